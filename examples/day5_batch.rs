@@ -0,0 +1,52 @@
+//! Compares batch point queries on `IntervalSet` against one tree walk per point.
+//!
+//! Run with `cargo run --release --example day5_batch [intervals] [points]`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use aoc25::day5::{Interval, IntervalSet, IntervalTree};
+
+fn main() {
+    let mut args = std::env::args().skip(1).map(|a| a.parse::<usize>().expect("sizes must be integers"));
+    let n = args.next().unwrap_or(200_000);
+    let m = args.next().unwrap_or(1_000_000);
+
+    let mut seed: u64 = 0x9E3779B97F4A7C15;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    const MAX: u64 = 1 << 48;
+    let intervals: Vec<Interval> = (0..n)
+        .map(|_| {
+            let start = next() % MAX;
+            Interval::new(start, start + next() % (MAX / (n as u64).max(1)))
+        })
+        .collect();
+    let points: Vec<u64> = (0..m).map(|_| next() % MAX).collect();
+    println!("{} intervals, {} points", n, m);
+
+    let (tree, build) = time(|| IntervalTree::from_intervals(intervals.iter().copied()));
+    let (tree_count, query) = time(|| points.iter().filter(|&&p| tree.contains_point(p)).count());
+    report("tree, per point", build, query);
+
+    let (set, build) = time(|| IntervalSet::from_intervals(intervals.iter().copied()));
+    let (membership, query) = time(|| set.contains_all(&points));
+    report("set, sorted sweep", build, query);
+
+    assert_eq!(tree_count, membership.count);
+    println!("{} points covered", membership.count);
+}
+
+fn time<R>(f: impl FnOnce() -> R) -> (R, Duration) {
+    let start = Instant::now();
+    let result = black_box(f());
+    (result, start.elapsed())
+}
+
+fn report(name: &str, build: Duration, query: Duration) {
+    println!("{:<18} build {:>10.2?}  query {:>10.2?}  total {:>10.2?}", name, build, query, build + query);
+}
@@ -61,9 +61,7 @@ impl Dial {
         let mut zero_count = complete_tours;
         match rotation {
             DialRotation::Left(_) => {
-                if prev_point < self.pointing && prev_point != 0 {
-                    zero_count += 1;
-                } else if self.pointing == 0 {
+                if (prev_point < self.pointing && prev_point != 0) || self.pointing == 0 {
                     zero_count += 1;
                 }
             }
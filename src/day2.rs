@@ -38,7 +38,7 @@ impl Id {
             v /= 10;
             dc += 1;
         }
-        if dc % 2 != 0 {
+        if !dc.is_multiple_of(2) {
             return true;
         }
         let patlen = dc / 2;
@@ -54,12 +54,12 @@ impl Id {
         assert!(n != 0, "length of an id cannot be zero");
         // return true if there is sequence at least repeated twice using window w
         let check_with_window = |w: usize| {
-            if n % w != 0 {
+            if !n.is_multiple_of(w) {
                 return false;
             }
             let mut i: usize = 0;
             let ss = &s[i..(i + w)];
-            i = i + w;
+            i += w;
             let mut at_least_two = false;
             while i < n {
                 let ss_next = &s[i..(i + w)];
@@ -69,14 +69,14 @@ impl Id {
                 i += w;
                 at_least_two = true;
             }
-            return at_least_two;
+            at_least_two
         };
         for w in 1..n {
             if check_with_window(w) {
                 return false;
             }
         }
-        return true;
+        true
     }
 }
 
@@ -1,84 +1,532 @@
+use anyhow::Context;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::ops::AddAssign;
 use std::str::FromStr;
+use std::sync::Arc;
 
 pub fn solve() -> anyhow::Result<()> {
-    let input = read_input()?;
-    let mut total_joltage = 0;
-    for bank in &input {
-        total_joltage += bank.max_joltage_part1();
-    }
-    println!("Total output joltage (PART 1): {}", total_joltage);
+    let input = BufReader::new(File::open("inputs/input3.txt")?);
+    let totals = solve_stream(input, &BankParser::default())?;
+    println!("Total output joltage (PART 1): {}", totals.part1);
+    println!("Total output joltage (PART 2): {}", totals.part2);
+    Ok(())
+}
 
-    let mut total_joltage = 0;
-    for bank in &input {
-        total_joltage += bank.max_joltage_part2();
+/// Total output joltage of both parts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Totals {
+    pub part1: Joltage,
+    pub part2: Joltage,
+}
+
+/// Computes both parts bank by bank while reading `reader` line by line, so memory
+/// stays bounded by the longest line regardless of input size.
+pub fn solve_stream<R: BufRead>(mut reader: R, parser: &BankParser) -> anyhow::Result<Totals> {
+    let mut totals = Totals::default();
+    let mut line = String::new();
+    let mut bank = Bank::default();
+    let mut line_no = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        line_no += 1;
+        let trimmed = line.trim_end_matches(['\n', '\r']);
+        parser.parse_into(trimmed, line_no, &mut bank)?;
+        totals.part1 += &bank.max_joltage_part1();
+        totals.part2 += &bank
+            .max_joltage_part2()
+            .with_context(|| format!("part 2 of bank at line {}", line_no))?;
     }
-    println!("Total output joltage (PART 2): {}", total_joltage);
-    Ok(())
+    Ok(totals)
 }
 
-/// Represents line of batteries, each battery is 1-9 ascii digit.
-struct Bank {
+/// Represents line of batteries, each battery is a rating of its [`Alphabet`],
+/// by default 1-9 ascii digit.
+#[derive(Debug, Clone, Default)]
+pub struct Bank {
+    /// ratings, i.e. ranks in `alphabet`
     batteries: Vec<u8>,
     len : usize,
+    alphabet: Alphabet,
 }
 impl FromStr for Bank {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut batteries = Vec::new();
-        for b in s.bytes() {
-            if b.is_ascii_digit() && b != b'0' {
-                batteries.push(b - b'0');
-            } else {
-                anyhow::bail!("invalid battery digit in line: '{}'", b);
+        BankParser::default().parse_line(s, 1)
+    }
+}
+
+/// Ordered set of symbols a battery rating can be written with, lowest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Alphabet {
+    /// `0-9`
+    #[default]
+    Decimal,
+    /// `0-9`, `a-f` (either case)
+    Hex,
+    /// Custom symbols, lowest rating first.
+    Custom(Arc<[char]>),
+}
+
+impl Alphabet {
+    /// Creates a custom alphabet from `symbols`, lowest rating first.
+    pub fn custom(symbols: &str) -> anyhow::Result<Self> {
+        let symbols: Vec<char> = symbols.chars().collect();
+        if symbols.len() < 2 || symbols.len() > 256 {
+            anyhow::bail!("alphabet must have 2 to 256 symbols, got {}", symbols.len());
+        }
+        for (i, c) in symbols.iter().enumerate() {
+            if symbols[..i].contains(c) {
+                anyhow::bail!("duplicate symbol '{}' in alphabet", c);
             }
         }
-        let len = batteries.len();
-        Ok(Self { batteries, len })
+        Ok(Alphabet::Custom(symbols.into()))
+    }
+
+    /// Rating of symbol `c`, if it belongs to the alphabet.
+    fn rank(&self, c: char) -> Option<u8> {
+        match self {
+            Alphabet::Decimal => c.to_digit(10).map(|d| d as u8),
+            Alphabet::Hex => c.to_digit(16).map(|d| d as u8),
+            Alphabet::Custom(symbols) => symbols.iter().position(|&s| s == c).map(|p| p as u8),
+        }
+    }
+
+    /// Symbol of rating `rank`.
+    fn symbol(&self, rank: u8) -> char {
+        match self {
+            Alphabet::Decimal | Alphabet::Hex => char::from_digit(rank as u32, self.radix()).unwrap(),
+            Alphabet::Custom(symbols) => symbols[rank as usize],
+        }
+    }
+
+    fn radix(&self) -> u32 {
+        match self {
+            Alphabet::Decimal => 10,
+            Alphabet::Hex => 16,
+            Alphabet::Custom(symbols) => symbols.len() as u32,
+        }
+    }
+}
+
+/// Parses [`Bank`]s. By default only `1-9` are accepted, as in the puzzle.
+#[derive(Debug, Clone, Default)]
+pub struct BankParser {
+    alphabet: Alphabet,
+    /// whether the lowest symbol of the alphabet (rating zero) is accepted
+    allow_zero: bool,
+}
+
+impl BankParser {
+    /// Parser for `alphabet`, rejecting zero ratings unless [`BankParser::allow_zero`] is set.
+    pub fn new(alphabet: Alphabet) -> Self {
+        Self { alphabet, allow_zero: false }
+    }
+
+    /// Accepts batteries rated zero.
+    pub fn allow_zero(mut self, allow: bool) -> Self {
+        self.allow_zero = allow;
+        self
+    }
+
+    /// Parses a bank from `line`; `line_no` (1-based) is only used in errors.
+    pub fn parse_line(&self, line: &str, line_no: usize) -> anyhow::Result<Bank> {
+        let mut bank = Bank::default();
+        self.parse_into(line, line_no, &mut bank)?;
+        Ok(bank)
+    }
+
+    /// Same as [`BankParser::parse_line`] but reuses the storage of `bank`.
+    pub fn parse_into(&self, line: &str, line_no: usize, bank: &mut Bank) -> anyhow::Result<()> {
+        bank.batteries.clear();
+        for (col, c) in line.chars().enumerate() {
+            match self.alphabet.rank(c) {
+                Some(rank) if rank != 0 || self.allow_zero => bank.batteries.push(rank),
+                _ => anyhow::bail!(
+                    "invalid battery {:?} at line {}, column {}",
+                    c,
+                    line_no,
+                    col + 1
+                ),
+            }
+        }
+        bank.len = bank.batteries.len();
+        if bank.alphabet != self.alphabet {
+            bank.alphabet = self.alphabet.clone();
+        }
+        Ok(())
+    }
+
+    /// Parses one bank per line of `content`.
+    pub fn parse_all(&self, content: &str) -> anyhow::Result<Vec<Bank>> {
+        content
+            .lines()
+            .enumerate()
+            .map(|(i, line)| self.parse_line(line, i + 1))
+            .collect() // collect handles Result<Vec> -> Vec<Result> transformation automagically
     }
 }
 impl Bank {
     /// Calculates and returns max joltage as specified in part 1.
-    fn max_joltage_part1(&self) -> u32 {
-        if self.len == 1 {
-            return self.batteries[0] as u32;
+    fn max_joltage_part1(&self) -> Joltage {
+        // a bank shorter than two batteries turns on all of them
+        self.max_joltage_exact(self.len.min(2)).expect("k is at most the bank length")
+    }
+
+    /// Calculates max joltage, as specified in part 2.
+    fn max_joltage_part2(&self) -> anyhow::Result<Joltage> {
+        self.max_joltage_exact(12)
+    }
+
+    /// Calculates the max joltage obtainable by turning on exactly `k` batteries.
+    /// Fails if the bank has fewer than `k` batteries, or if the result does not fit in `u64`
+    /// (use [`Bank::max_joltage_exact`] for large `k`).
+    pub fn max_joltage(&self, k: usize) -> anyhow::Result<u64> {
+        self.max_joltage_exact(k)?.to_int()
+    }
+
+    /// Same as [`Bank::max_joltage`] but exact for any `k`.
+    pub fn max_joltage_exact(&self, k: usize) -> anyhow::Result<Joltage> {
+        Ok(self.joltage(&self.max_selection(k)?))
+    }
+
+    /// Calculates the min joltage obtainable by turning on exactly `k` batteries.
+    pub fn min_joltage(&self, k: usize) -> anyhow::Result<u64> {
+        self.joltage(&self.select(k, Objective::Min, Constraint::None)?).to_int()
+    }
+
+    /// Max joltage of `k` batteries, with at most `at_most` of them in any `window` consecutive ones.
+    pub fn max_joltage_windowed(&self, k: usize, window: usize, at_most: usize) -> anyhow::Result<u64> {
+        let constraint = Constraint::Window { len: window, at_most };
+        self.joltage(&self.select(k, Objective::Max, constraint)?).to_int()
+    }
+
+    /// Max joltage of `k` batteries, one of which must be the battery at `required`.
+    pub fn max_joltage_with(&self, k: usize, required: usize) -> anyhow::Result<u64> {
+        self.joltage(&self.select(k, Objective::Max, Constraint::Required(required))?).to_int()
+    }
+
+    /// Returns positions (ascending) of the `k` batteries giving the max joltage.
+    pub fn max_selection(&self, k: usize) -> anyhow::Result<Vec<usize>> {
+        self.select(k, Objective::Max, Constraint::None)
+    }
+
+    /// Returns positions (ascending) of the `k` batteries that are best for `objective`
+    /// while satisfying `constraint`. Fails if no such selection exists.
+    pub fn select(&self, k: usize, objective: Objective, constraint: Constraint) -> anyhow::Result<Vec<usize>> {
+        if k > self.len {
+            anyhow::bail!("cannot turn on {} batteries in a bank of {}", k, self.len);
         }
-        if self.len == 0 {
-            return 0;
+        let selection = match constraint {
+            Constraint::None => Some(best_subsequence(&self.batteries, k, objective)),
+            Constraint::Window { len: 0, .. } => anyhow::bail!("window length cannot be zero"),
+            Constraint::Window { len, at_most } => select_windowed(&self.batteries, k, objective, len, at_most),
+            Constraint::Required(r) if r >= self.len => {
+                anyhow::bail!("required battery {} is outside the bank of {}", r, self.len)
+            }
+            Constraint::Required(r) => select_required(&self.batteries, k, objective, r),
+        };
+        selection.ok_or(anyhow::anyhow!("no selection of {} batteries satisfies {:?}", k, constraint))
+    }
+
+    /// Joltage of the batteries at `positions`, read left to right.
+    pub fn joltage(&self, positions: &[usize]) -> Joltage {
+        let ratings = positions.iter().map(|&pos| self.batteries[pos]);
+        Joltage::from_ratings(ratings, self.alphabet.radix())
+    }
+
+    /// Renders the bank line with batteries at `positions` (ascending) wrapped in brackets,
+    /// e.g. `[9]1[9]1`. Hex ratings are rendered lowercase.
+    pub fn highlight(&self, positions: &[usize]) -> String {
+        let mut out = String::with_capacity(self.len + positions.len() * 2);
+        let mut selected = positions.iter().peekable();
+        for (i, &b) in self.batteries.iter().enumerate() {
+            let digit = self.alphabet.symbol(b);
+            if selected.next_if_eq(&&i).is_some() {
+                out.push('[');
+                out.push(digit);
+                out.push(']');
+            } else {
+                out.push(digit);
+            }
         }
-        // find max, the first in equality
-        let (pos, max_left) = find_first_max(&self.batteries[..(self.len - 1)]).unwrap();
-        let &max_right = self.batteries[(pos + 1)..].iter().max().unwrap();
-        (*max_left as u32) * 10 + (max_right as u32)
+        out
     }
-    //987654321111111
-    /// Calculates max joltage, as specified in part 2.
-    fn max_joltage_part2(&self) -> u64 {
-        assert!(self.len > 12, "In part2, Bank length should be greater than 12");
-        let mut bats = Vec::new();
-        let mut pos : Option<usize> = None;
-        for offset in (0..12).rev() {
-            let start = pos.map(|p| p + 1).unwrap_or(0);
-            let slice = &self.batteries[start..(self.len - offset)];
-            let (max_pos, max_val) = find_first_max(slice).unwrap(); // SAFETY: slice not empty
-            bats.push(*max_val as u64);
-            pos = Some(max_pos + start);
+}
+
+/// Exact, arbitrarily wide joltage kept as decimal digits (least significant first).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Joltage {
+    // no leading zeros (trailing in storage), zero is empty
+    digits: Vec<u8>,
+}
+
+impl Ord for Joltage {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        // without leading zeros, longer means larger
+        self.digits
+            .len()
+            .cmp(&other.digits.len())
+            .then_with(|| self.digits.iter().rev().cmp(other.digits.iter().rev()))
+    }
+}
+
+impl PartialOrd for Joltage {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Joltage {
+    /// Value of `ratings` read as a number in base `radix`, most significant first.
+    /// O(k) for decimal ratings, which already are the digits, O(k²) otherwise.
+    fn from_ratings(ratings: impl Iterator<Item = u8>, radix: u32) -> Self {
+        if radix == 10 {
+            let mut digits: Vec<u8> = ratings.collect();
+            digits.reverse();
+            let len = digits.iter().rposition(|&d| d != 0).map_or(0, |i| i + 1);
+            digits.truncate(len);
+            return Self { digits };
+        }
+        let mut joltage = Joltage::default();
+        for rating in ratings {
+            joltage.mul_add(radix, rating as u32);
+        }
+        joltage
+    }
+
+    /// `self = self * mul + add`
+    fn mul_add(&mut self, mul: u32, add: u32) {
+        let mut carry = add;
+        for d in self.digits.iter_mut() {
+            let v = *d as u32 * mul + carry;
+            *d = (v % 10) as u8;
+            carry = v / 10;
+        }
+        while carry > 0 {
+            self.digits.push((carry % 10) as u8);
+            carry /= 10;
         }
-        bats.into_iter()
-            .fold(0, |acc, digit| acc * 10 + digit)
+        // a zero value keeps no digits
+        while self.digits.last() == Some(&0) {
+            self.digits.pop();
+        }
+    }
+
+    /// Converts to an integer type, failing instead of wrapping around if it does not fit.
+    pub fn to_int<T: JoltageInt>(&self) -> anyhow::Result<T> {
+        self.digits
+            .iter()
+            .rev()
+            .try_fold(T::ZERO, |acc, &d| acc.push_digit(d))
+            .ok_or(anyhow::anyhow!(
+                "joltage {} does not fit in {}",
+                self,
+                std::any::type_name::<T>()
+            ))
     }
 }
 
-fn read_input() -> anyhow::Result<Vec<Bank>> {
-    std::fs::read_to_string("inputs/input3.txt")?
-        .lines()
-        .map(|line| line.parse()) // parse returns Result
-        .collect() // collect handles Result<Vec> -> Vec<Result> transformation automagically
+impl From<u128> for Joltage {
+    fn from(mut value: u128) -> Self {
+        let mut digits = Vec::new();
+        while value > 0 {
+            digits.push((value % 10) as u8);
+            value /= 10;
+        }
+        Self { digits }
+    }
 }
 
-fn find_first_max<T : Ord>(slice : &[T]) -> Option<(usize, &T)> {
-    let max = slice.iter().max()?;
-    let pos = slice.iter().position(|i| i == max)?;
-    Some((pos, max))
+impl AddAssign<&Joltage> for Joltage {
+    fn add_assign(&mut self, rhs: &Joltage) {
+        let len = self.digits.len().max(rhs.digits.len());
+        let mut sum = Vec::with_capacity(len + 1);
+        let mut carry = 0;
+        let mut lhs_digits = self.digits.iter();
+        let mut rhs_digits = rhs.digits.iter();
+        for _ in 0..len {
+            let d = lhs_digits.next().unwrap_or(&0) + rhs_digits.next().unwrap_or(&0) + carry;
+            sum.push(d % 10);
+            carry = d / 10;
+        }
+        if carry > 0 {
+            sum.push(carry);
+        }
+        self.digits = sum;
+    }
+}
+
+impl fmt::Display for Joltage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.digits.is_empty() {
+            return write!(f, "0");
+        }
+        for d in self.digits.iter().rev() {
+            write!(f, "{}", d)?;
+        }
+        Ok(())
+    }
+}
+
+/// Unsigned integer types a [`Joltage`] can be converted into.
+pub trait JoltageInt: Sized {
+    const ZERO: Self;
+    /// Returns `self * 10 + digit`, or `None` on overflow.
+    fn push_digit(self, digit: u8) -> Option<Self>;
+}
+
+macro_rules! impl_joltage_int {
+    ($($t:ty),*) => {
+        $(impl JoltageInt for $t {
+            const ZERO: Self = 0;
+            #[inline]
+            fn push_digit(self, digit: u8) -> Option<Self> {
+                self.checked_mul(10)?.checked_add(digit as $t)
+            }
+        })*
+    };
+}
+impl_joltage_int!(u8, u16, u32, u64, u128, usize);
+
+/// Whether a selection aims for the largest or the smallest joltage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Objective {
+    Max,
+    Min,
+}
+
+impl Objective {
+    /// True if digit `a` is strictly better than `b`.
+    #[inline]
+    fn prefers(&self, a: u8, b: u8) -> bool {
+        match self {
+            Objective::Max => a > b,
+            Objective::Min => a < b,
+        }
+    }
+}
+
+/// Extra rule a battery selection must satisfy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Constraint {
+    None,
+    /// At most `at_most` selected batteries in any `len` consecutive ones.
+    Window { len: usize, at_most: usize },
+    /// Battery at this position must be selected.
+    Required(usize),
+}
+
+/// Returns positions of the `k` digits forming the best number for `objective` while
+/// keeping their order. Runs in O(n) with a monotonic stack; on ties the leftmost digit
+/// is chosen. Caller must ensure `k <= digits.len()`.
+fn best_subsequence(digits: &[u8], k: usize, objective: Objective) -> Vec<usize> {
+    let n = digits.len();
+    let mut stack: Vec<usize> = Vec::with_capacity(k);
+    for (i, &d) in digits.iter().enumerate() {
+        // pop worse digits while enough digits remain to fill k
+        while let Some(&top) = stack.last() {
+            if objective.prefers(d, digits[top]) && stack.len() - 1 + (n - i) >= k {
+                stack.pop();
+            } else {
+                break;
+            }
+        }
+        if stack.len() < k {
+            stack.push(i);
+        }
+    }
+    stack
+}
+
+/// Best selection of `k` digits that includes position `required`: tries every split of
+/// the remaining digits around it, each side solved by [`best_subsequence`]. O(k·n).
+fn select_required(digits: &[u8], k: usize, objective: Objective, required: usize) -> Option<Vec<usize>> {
+    let (left, right) = (&digits[..required], &digits[(required + 1)..]);
+    let mut best: Option<Vec<usize>> = None;
+    for taken_left in 0..=left.len().min(k.checked_sub(1)?) {
+        let taken_right = k - 1 - taken_left;
+        if taken_right > right.len() {
+            continue;
+        }
+        let mut candidate = best_subsequence(left, taken_left, objective);
+        candidate.push(required);
+        candidate.extend(
+            best_subsequence(right, taken_right, objective)
+                .into_iter()
+                .map(|p| p + required + 1),
+        );
+        let better = match &best {
+            None => true,
+            Some(b) => candidate
+                .iter()
+                .zip(b)
+                .map(|(&c, &b)| (digits[c], digits[b]))
+                .find(|(c, b)| c != b)
+                .is_some_and(|(c, b)| objective.prefers(c, b)),
+        };
+        if better {
+            best = Some(candidate);
+        }
+    }
+    best
+}
+
+/// Best selection of `k` digits with at most `at_most` of them in any `len` consecutive
+/// positions. Picks digits one at a time, taking the leftmost best digit whose choice can
+/// still be completed. O(k·n²) at worst.
+fn select_windowed(digits: &[u8], k: usize, objective: Objective, len: usize, at_most: usize) -> Option<Vec<usize>> {
+    // number of placed positions in the window ending at q
+    let in_window = |placed: &[usize], q: usize| placed.iter().rev().take_while(|&&p| p + len > q).count();
+    // placing the rest as early as possible fits the most, in O(n) with a running window
+    let completes = |chosen: &[usize]| {
+        let mut placed = chosen.len();
+        let mut window: VecDeque<usize> = chosen.iter().rev().take(at_most).rev().copied().collect();
+        let from = chosen.last().map_or(0, |&last| last + 1);
+        for q in from..digits.len() {
+            if placed == k {
+                break;
+            }
+            while window.front().is_some_and(|&p| p + len <= q) {
+                window.pop_front();
+            }
+            if window.len() < at_most {
+                window.push_back(q);
+                placed += 1;
+            }
+        }
+        placed == k
+    };
+
+    let mut chosen = Vec::with_capacity(k);
+    let mut start = 0;
+    for _ in 0..k {
+        let mut best: Option<usize> = None;
+        for p in start..digits.len() {
+            if let Some(b) = best
+                && !objective.prefers(digits[p], digits[b])
+            {
+                continue;
+            }
+            if in_window(&chosen, p) >= at_most {
+                continue;
+            }
+            chosen.push(p);
+            if completes(&chosen) {
+                best = Some(p);
+            }
+            chosen.pop();
+        }
+        let p = best?;
+        chosen.push(p);
+        start = p + 1;
+    }
+    Some(chosen)
 }
 
 #[cfg(test)]
@@ -88,37 +536,227 @@ mod test {
     #[test]
     fn test_joltage() {
         let bank : Bank = "987654321111111".parse().unwrap();
-        assert_eq!(bank.max_joltage_part1(), 98);
+        assert_eq!(bank.max_joltage_part1(), 98.into());
         let bank : Bank = "811111111111119".parse().unwrap();
-        assert_eq!(bank.max_joltage_part1(), 89);
+        assert_eq!(bank.max_joltage_part1(), 89.into());
         let bank : Bank = "234234234234278".parse().unwrap();
-        assert_eq!(bank.max_joltage_part1(), 78);
+        assert_eq!(bank.max_joltage_part1(), 78.into());
         let bank : Bank = "111111111111111".parse().unwrap();
-        assert_eq!(bank.max_joltage_part1(), 11);
+        assert_eq!(bank.max_joltage_part1(), 11.into());
         let bank : Bank = "123456789".parse().unwrap();
-        assert_eq!(bank.max_joltage_part1(), 89);
+        assert_eq!(bank.max_joltage_part1(), 89.into());
         let bank : Bank = "9191".parse().unwrap();
-        assert_eq!(bank.max_joltage_part1(), 99);
+        assert_eq!(bank.max_joltage_part1(), 99.into());
         let bank : Bank = "12".parse().unwrap();
-        assert_eq!(bank.max_joltage_part1(), 12);
+        assert_eq!(bank.max_joltage_part1(), 12.into());
         let bank : Bank = "1".parse().unwrap();
-        assert_eq!(bank.max_joltage_part1(), 1);
+        assert_eq!(bank.max_joltage_part1(), 1.into());
         let bank : Bank = "".parse().unwrap();
-        assert_eq!(bank.max_joltage_part1(), 0);
+        assert_eq!(bank.max_joltage_part1(), 0.into());
     }
     #[test]
     fn test_joltage_p2() {
         let bank : Bank = "987654321111111".parse().unwrap();
-        assert_eq!(bank.max_joltage_part2(), 987654321111);
+        assert_eq!(bank.max_joltage_part2().unwrap(), 987654321111.into());
 
         let bank : Bank = "811111111111119".parse().unwrap();
-        assert_eq!(bank.max_joltage_part2(), 811111111119);
+        assert_eq!(bank.max_joltage_part2().unwrap(), 811111111119.into());
 
         let bank : Bank = "234234234234278".parse().unwrap();
-        assert_eq!(bank.max_joltage_part2(), 434234234278);
+        assert_eq!(bank.max_joltage_part2().unwrap(), 434234234278.into());
 
         let bank : Bank = "818181911112111".parse().unwrap();
-        assert_eq!(bank.max_joltage_part2(), 888911112111);
+        assert_eq!(bank.max_joltage_part2().unwrap(), 888911112111.into());
+
+    }
+
+    /// All numbers made of exactly `k` digits of `digits` whose positions pass `keep`.
+    fn brute_force(digits: &[u8], k: usize, keep: impl Fn(&[usize]) -> bool) -> Vec<u64> {
+        let n = digits.len();
+        (0u32..(1 << n))
+            .filter(|mask| mask.count_ones() as usize == k)
+            .map(|mask| (0..n).filter(|i| mask & (1 << i) != 0).collect::<Vec<_>>())
+            .filter(|positions| keep(positions))
+            .map(|positions| positions.iter().fold(0, |acc, &i| acc * 10 + digits[i] as u64))
+            .collect()
+    }
+
+    /// Deterministic random banks (xorshift) of at most 10 batteries.
+    fn random_banks(count: usize) -> Vec<String> {
+        let mut next = crate::test_rng::xorshift(0x2545F491);
+        (0..count)
+            .map(|_| {
+                let len = (next() % 11) as usize;
+                (0..len).map(|_| char::from(b'1' + (next() % 9) as u8)).collect()
+            })
+            .collect()
+    }
 
+    #[test]
+    fn test_joltage_k() {
+        let bank : Bank = "987654321111111".parse().unwrap();
+        assert_eq!(bank.max_joltage(0).unwrap(), 0);
+        assert_eq!(bank.max_joltage(1).unwrap(), 9);
+        assert_eq!(bank.max_joltage(15).unwrap(), 987654321111111);
+        assert!(bank.max_joltage(16).is_err());
+
+        let bank : Bank = "12".parse().unwrap();
+        assert!(bank.max_joltage_part2().is_err());
+    }
+
+    #[test]
+    fn test_wide_joltage() {
+        let line = "9".repeat(25) + "1";
+        let bank : Bank = line.parse().unwrap();
+        let joltage = bank.max_joltage_exact(25).unwrap();
+        assert_eq!(joltage.to_string(), "9".repeat(25));
+        assert_eq!(joltage.to_int::<u128>().unwrap(), 10u128.pow(25) - 1);
+        assert!(joltage.to_int::<u64>().is_err());
+        assert!(bank.max_joltage(20).is_err());
+        assert_eq!(bank.max_joltage(19).unwrap(), 9_999_999_999_999_999_999);
+
+        let mut total = Joltage::default();
+        assert_eq!(total.to_string(), "0");
+        total += &joltage;
+        total += &Joltage::from(1);
+        assert_eq!(total.to_string(), format!("1{}", "0".repeat(25)));
+        assert_eq!(total, Joltage::from(10u128.pow(25)));
+        assert!(total > joltage);
+        assert_eq!(bank.max_joltage_part1().to_int::<u8>().unwrap(), 99);
+    }
+
+    #[test]
+    fn test_joltage_digits() {
+        let bank: Bank = "91819".parse().unwrap();
+        assert_eq!(bank.max_joltage_exact(5).unwrap().to_string(), "91819");
+        assert_eq!(bank.max_joltage_exact(3).unwrap(), Joltage::from(989));
+        assert_eq!(Joltage::from(0), Joltage::default());
+
+        let zeros = BankParser::default().allow_zero(true).parse_line("00120", 1).unwrap();
+        assert_eq!(zeros.max_joltage_exact(5).unwrap().to_string(), "120");
+        assert_eq!(zeros.max_joltage_exact(2).unwrap(), Joltage::from(20));
+        assert_eq!(BankParser::default().allow_zero(true).parse_line("000", 1).unwrap().max_joltage_exact(3).unwrap(), Joltage::default());
+
+        let hex = BankParser::new(Alphabet::Hex).allow_zero(true).parse_line("f0a1", 1).unwrap();
+        assert_eq!(hex.max_joltage_exact(4).unwrap(), Joltage::from(0xf0a1));
+        assert_eq!(hex.max_joltage_exact(3).unwrap(), Joltage::from(0xfa1));
+
+        assert!(Joltage::from(19) < Joltage::from(91));
+        assert!(Joltage::from(100) > Joltage::from(99));
+        assert!(Joltage::from(1) > Joltage::default());
+
+        // large k stays linear
+        let line: String = (0..200_000).map(|i| char::from(b'1' + (i % 9) as u8)).collect();
+        let bank: Bank = line.parse().unwrap();
+        let joltage = bank.max_joltage_exact(150_000).unwrap();
+        assert_eq!(joltage.to_string().len(), 150_000);
+    }
+
+    #[test]
+    fn test_parser() {
+        let err = "12a4".parse::<Bank>().unwrap_err();
+        assert_eq!(err.to_string(), "invalid battery 'a' at line 1, column 3");
+        assert!("102".parse::<Bank>().is_err());
+
+        let parser = BankParser::default().allow_zero(true);
+        let bank = parser.parse_line("102", 1).unwrap();
+        assert_eq!(bank.max_joltage(2).unwrap(), 12);
+        assert_eq!(bank.min_joltage(2).unwrap(), 2);
+        let err = parser.parse_all("12\n3x").unwrap_err();
+        assert_eq!(err.to_string(), "invalid battery 'x' at line 2, column 2");
+
+        let parser = BankParser::new(Alphabet::Hex);
+        let bank = parser.parse_line("1fA2", 1).unwrap();
+        assert_eq!(bank.max_joltage(2).unwrap(), 0xfa);
+        assert_eq!(bank.highlight(&bank.max_selection(2).unwrap()), "1[f][a]2");
+        assert!(parser.parse_line("0f", 1).is_err());
+
+        let parser = BankParser::new(Alphabet::custom("-+*").unwrap()).allow_zero(true);
+        let bank = parser.parse_line("+-*+", 1).unwrap();
+        // base 3: *+ = 2 * 3 + 1
+        assert_eq!(bank.max_joltage(2).unwrap(), 7);
+        assert_eq!(bank.highlight(&[2, 3]), "+-[*][+]");
+        assert!(Alphabet::custom("ab a").is_err());
+        assert!(Alphabet::custom("a").is_err());
+    }
+
+    #[test]
+    fn test_solve_stream() {
+        let input = "987654321111111\n811111111111119\r\n234234234234278\n818181911112111\n";
+        let totals = solve_stream(input.as_bytes(), &BankParser::default()).unwrap();
+        assert_eq!(totals.part1, 357.into());
+        assert_eq!(totals.part2, 3121910778619.into());
+
+        let err = solve_stream("987654321111111\n12\n".as_bytes(), &BankParser::default()).unwrap_err();
+        assert_eq!(format!("{:#}", err), "part 2 of bank at line 2: cannot turn on 12 batteries in a bank of 2");
+    }
+
+    #[test]
+    fn test_selection() {
+        let bank : Bank = "818181911112111".parse().unwrap();
+        let positions = bank.max_selection(12).unwrap();
+        assert_eq!(positions, vec![0, 2, 4, 6, 7, 8, 9, 10, 11, 12, 13, 14]);
+        assert_eq!(bank.highlight(&positions), "[8]1[8]1[8]1[9][1][1][1][1][2][1][1][1]");
+
+        let bank : Bank = "9191".parse().unwrap();
+        let positions = bank.max_selection(2).unwrap();
+        assert_eq!(positions, vec![0, 2]);
+        assert_eq!(bank.highlight(&positions), "[9]1[9]1");
+        assert!(bank.max_selection(5).is_err());
+    }
+
+    #[test]
+    fn test_constrained_joltage() {
+        let bank : Bank = "5511".parse().unwrap();
+        assert_eq!(bank.min_joltage(2).unwrap(), 11);
+        assert_eq!(bank.max_joltage_with(3, 1).unwrap(), 551);
+        assert_eq!(bank.max_joltage_with(3, 3).unwrap(), 551);
+        assert_eq!(bank.max_joltage_windowed(2, 2, 1).unwrap(), 51);
+        assert!(bank.max_joltage_windowed(3, 2, 1).is_err());
+        assert!(bank.max_joltage_windowed(2, 0, 1).is_err());
+        assert!(bank.max_joltage_with(2, 4).is_err());
+    }
+
+    #[test]
+    fn test_joltage_against_brute_force() {
+        for line in random_banks(500) {
+            let bank : Bank = line.parse().unwrap();
+            let len = bank.len;
+            for k in 0..=len {
+                let all = brute_force(&bank.batteries, k, |_| true);
+                let max = all.iter().max().copied().unwrap_or(0);
+                let min = all.iter().min().copied().unwrap_or(0);
+                assert_eq!(bank.max_joltage(k).unwrap(), max, "bank: {}, k: {}", line, k);
+                assert_eq!(bank.min_joltage(k).unwrap(), min, "bank: {}, k: {}", line, k);
+            }
+        }
+    }
+
+    #[test]
+    fn test_constrained_against_brute_force() {
+        for line in random_banks(200) {
+            let bank : Bank = line.parse().unwrap();
+            let len = bank.len;
+            for k in 1..=len {
+                for r in 0..len {
+                    let best = brute_force(&bank.batteries, k, |ps| ps.contains(&r)).into_iter().max();
+                    assert_eq!(bank.max_joltage_with(k, r).ok(), best, "bank: {}, k: {}, r: {}", line, k, r);
+                }
+                for window in 1..=3 {
+                    for at_most in 0..=2 {
+                        let best = brute_force(&bank.batteries, k, |ps| {
+                            ps.iter().all(|&q| ps.iter().filter(|&&p| p <= q && p + window > q).count() <= at_most)
+                        })
+                        .into_iter()
+                        .max();
+                        assert_eq!(
+                            bank.max_joltage_windowed(k, window, at_most).ok(),
+                            best,
+                            "bank: {}, k: {}, window: {}, at_most: {}", line, k, window, at_most
+                        );
+                    }
+                }
+            }
+        }
     }
 }
@@ -12,64 +12,714 @@ pub fn solve() -> anyhow::Result<()> {
 }
 
 use ahash::AHashMap;
+use std::cell::OnceCell;
 use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Grids whose bounding box has at most this many cells may use [`DenseGrid`].
+const DENSE_MAX_CELLS: usize = 1 << 28;
+/// [`RollGrid::reinforce`] searches exactly only with at most this many empty cells...
+const EXACT_MAX_CANDIDATES: usize = 64;
+/// ...and gives up on exactness after visiting this many search nodes.
+const EXACT_NODE_BUDGET: usize = 200_000;
 
 #[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
-struct Roll {
-    pos: (i32, i32),
+pub struct Roll {
+    pub pos: (i32, i32),
 }
 
 impl Roll {
+    pub fn new(x: i32, y: i32) -> Self {
+        Self { pos: (x, y) }
+    }
+
     #[inline]
-    fn neighbors(&self) -> impl Iterator<Item = Roll> {
+    fn neighbors<'a>(&self, offsets: &'a [(i32, i32)]) -> impl Iterator<Item = Roll> + 'a {
         let (x, y) = self.pos;
-        (-1..=1).flat_map(move |dy| {
-            (-1..=1).filter_map(move |dx| {
-                if dx == 0 && dy == 0 {
-                    None
-                } else {
-                    Some(Roll { pos: (x + dx, y + dy) })
+        offsets.iter().map(move |(dx, dy)| Roll { pos: (x + dx, y + dy) })
+    }
+
+    /// Cells that have this roll among their neighbors; same as
+    /// [`Roll::neighbors`] unless the offsets are asymmetric.
+    #[inline]
+    fn dependents<'a>(&self, offsets: &'a [(i32, i32)]) -> impl Iterator<Item = Roll> + 'a {
+        let (x, y) = self.pos;
+        offsets.iter().map(move |(dx, dy)| Roll { pos: (x - dx, y - dy) })
+    }
+}
+
+/// Which cells around a roll count as its neighbors.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Neighborhood {
+    /// 8 surrounding cells.
+    #[default]
+    Moore,
+    /// 4 orthogonal cells.
+    VonNeumann,
+    /// All cells within Chebyshev distance r (at most 127).
+    Radius(u8),
+    /// 6 cells of a hex grid in axial coordinates.
+    Hex,
+    /// Arbitrary offsets; `(0, 0)` and duplicates are ignored.
+    Offsets(Vec<(i32, i32)>),
+}
+
+impl Neighborhood {
+    pub fn offsets(&self) -> Vec<(i32, i32)> {
+        let square = |r: i32| {
+            (-r..=r)
+                .flat_map(move |dy| (-r..=r).map(move |dx| (dx, dy)))
+                .filter(|&o| o != (0, 0))
+                .collect()
+        };
+        match self {
+            Neighborhood::Moore => square(1),
+            Neighborhood::VonNeumann => vec![(0, -1), (-1, 0), (1, 0), (0, 1)],
+            Neighborhood::Radius(r) => square((*r).min(127) as i32),
+            Neighborhood::Hex => vec![(1, 0), (-1, 0), (0, 1), (0, -1), (1, -1), (-1, 1)],
+            Neighborhood::Offsets(offsets) => {
+                let mut unique = Vec::with_capacity(offsets.len());
+                for &o in offsets {
+                    if o != (0, 0) && !unique.contains(&o) {
+                        unique.push(o);
+                    }
                 }
-            })
+                unique
+            }
+        }
+    }
+
+    /// Whether this is exactly the 8-cell neighborhood.
+    fn is_moore(&self) -> bool {
+        matches!(self, Neighborhood::Moore | Neighborhood::Radius(1))
+    }
+}
+
+/// How a roll's neighbor count is compared to the threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Less,
+    LessEq,
+    Equal,
+    GreaterEq,
+    Greater,
+}
+
+/// When a roll is accessible: `neighbor count <comparison> threshold`.
+/// The puzzle rule is fewer than 4 neighbors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Accessibility {
+    pub comparison: Comparison,
+    pub threshold: u16,
+}
+
+impl Default for Accessibility {
+    fn default() -> Self {
+        Self { comparison: Comparison::Less, threshold: 4 }
+    }
+}
+
+impl Accessibility {
+    pub fn new(comparison: Comparison, threshold: u16) -> Self {
+        Self { comparison, threshold }
+    }
+
+    #[inline]
+    pub fn accepts(&self, count: u16) -> bool {
+        match self.comparison {
+            Comparison::Less => count < self.threshold,
+            Comparison::LessEq => count <= self.threshold,
+            Comparison::Equal => count == self.threshold,
+            Comparison::GreaterEq => count >= self.threshold,
+            Comparison::Greater => count > self.threshold,
+        }
+    }
+}
+
+/// Axis aligned rectangle of cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    /// position of the top-left cell
+    pub origin: (i32, i32),
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    /// Smallest rectangle containing all `rolls`, `None` if there are none.
+    pub fn enclosing(rolls: impl IntoIterator<Item = Roll>) -> Option<Rect> {
+        let mut rolls = rolls.into_iter();
+        let first = rolls.next()?;
+        let (mut min, mut max) = (first.pos, first.pos);
+        for r in rolls {
+            min = (min.0.min(r.pos.0), min.1.min(r.pos.1));
+            max = (max.0.max(r.pos.0), max.1.max(r.pos.1));
+        }
+        Some(Rect {
+            origin: min,
+            width: (max.0 as i64 - min.0 as i64 + 1) as usize,
+            height: (max.1 as i64 - min.1 as i64 + 1) as usize,
         })
     }
+
+    pub fn contains(&self, pos: &Roll) -> bool {
+        rect_cell(self.origin, self.width, self.height, pos).is_some()
+    }
+
+    /// Smallest rectangle containing both, ignoring empty ones.
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.width == 0 || self.height == 0 {
+            return *other;
+        }
+        if other.width == 0 || other.height == 0 {
+            return *self;
+        }
+        let far = |r: &Rect| (r.origin.0 as i64 + r.width as i64, r.origin.1 as i64 + r.height as i64);
+        let origin = (self.origin.0.min(other.origin.0), self.origin.1.min(other.origin.1));
+        let (fa, fb) = (far(self), far(other));
+        Rect {
+            origin,
+            width: (fa.0.max(fb.0) - origin.0 as i64) as usize,
+            height: (fa.1.max(fb.1) - origin.1 as i64) as usize,
+        }
+    }
+
+    /// Cells in row-major order.
+    pub fn cells(&self) -> impl Iterator<Item = Roll> + '_ {
+        let (ox, oy) = self.origin;
+        (0..self.height as i32).flat_map(move |y| (0..self.width as i32).map(move |x| Roll::new(ox + x, oy + y)))
+    }
 }
 
-#[derive(Debug, Clone)]
-struct RollGrid {
+/// What cells outside a bounded grid count as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Walls {
+    #[default]
+    Empty,
+    Occupied,
+}
+
+/// Shape of the plane the rolls live on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Topology {
+    /// Infinite plane, cells without a roll are empty.
+    #[default]
+    Unbounded,
+    /// Only cells of `area` exist, neighbors outside of it count as `walls`.
+    Bounded { area: Rect, walls: Walls },
+    /// `area` wraps around on both axes. Offsets that wrap back onto a roll itself, on an
+    /// area narrower than the neighborhood, do not count.
+    Torus(Rect),
+}
+
+impl Topology {
+    /// Cell a neighbor position refers to, `None` if it is outside the grid.
+    #[inline]
+    fn resolve(&self, pos: Roll) -> Option<Roll> {
+        match self {
+            Topology::Unbounded => Some(pos),
+            Topology::Bounded { area, .. } => Some(pos).filter(|p| area.contains(p)),
+            Topology::Torus(area) => {
+                let (ox, oy) = area.origin;
+                let x = (pos.pos.0 as i64 - ox as i64).rem_euclid(area.width as i64);
+                let y = (pos.pos.1 as i64 - oy as i64).rem_euclid(area.height as i64);
+                Some(Roll::new(ox + x as i32, oy + y as i32))
+            }
+        }
+    }
+
+    /// Whether a roll can be placed at `pos`.
+    fn allows(&self, pos: &Roll) -> bool {
+        match self {
+            Topology::Unbounded => true,
+            Topology::Bounded { area, .. } | Topology::Torus(area) => area.contains(pos),
+        }
+    }
+}
+
+/// Storage backend of a [`RollGrid`].
+pub trait RollStore: std::fmt::Debug {
+    fn contains(&self, roll: &Roll) -> bool;
+    /// Returns false if the roll was already there.
+    /// Panics if the store cannot hold `roll`, see [`RollStore::can_hold`].
+    fn insert(&mut self, roll: Roll) -> bool;
+    /// Returns false if there was no such roll.
+    fn remove(&mut self, roll: &Roll) -> bool;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = Roll> + '_>;
+    /// Whether `roll` can be inserted.
+    fn can_hold(&self, _roll: &Roll) -> bool {
+        true
+    }
+    fn is_dense(&self) -> bool {
+        false
+    }
+    /// Area the rolls are in, `None` if unknown because there are no rolls.
+    fn bounds(&self) -> Option<Rect> {
+        Rect::enclosing(self.iter())
+    }
+    /// Number of neighbors of every roll.
+    fn neighbor_counts(&self, neighborhood: &Neighborhood) -> NeighborCounts {
+        count_neighbors(self, &neighborhood.offsets())
+    }
+    fn clone_box(&self) -> Box<dyn RollStore>;
+}
+
+/// Counts neighbors of every roll in `store` with one lookup per offset.
+fn count_neighbors<S: RollStore + ?Sized>(store: &S, offsets: &[(i32, i32)]) -> NeighborCounts {
+    let mut counts = AHashMap::with_capacity(store.len());
+    for r in store.iter() {
+        let count = r.neighbors(offsets).filter(|n| store.contains(n)).count();
+        counts.insert(r, count as u16);
+    }
+    NeighborCounts::Sparse(counts)
+}
+
+impl Clone for Box<dyn RollStore> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Hash set backend, for rolls anywhere on the plane.
+#[derive(Debug, Clone, Default)]
+pub struct SparseGrid {
     rolls: AHashSet<Roll>,
 }
 
-impl RollGrid {
-    fn accessible_rolls(&self) -> usize {
-        self.rolls
-            .iter()
-            .filter(|r| self.count_neighbors(*r) < 4)
-            .count()
+impl RollStore for SparseGrid {
+    fn contains(&self, roll: &Roll) -> bool {
+        self.rolls.contains(roll)
+    }
+    fn insert(&mut self, roll: Roll) -> bool {
+        self.rolls.insert(roll)
+    }
+    fn remove(&mut self, roll: &Roll) -> bool {
+        self.rolls.remove(roll)
+    }
+    fn len(&self) -> usize {
+        self.rolls.len()
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = Roll> + '_> {
+        Box::new(self.rolls.iter().copied())
     }
+    fn clone_box(&self) -> Box<dyn RollStore> {
+        Box::new(self.clone())
+    }
+}
 
+/// Row-major bitset backend for rolls inside a fixed rectangle.
+/// Neighbor counts are computed a row word (64 cells) at a time.
+#[derive(Debug, Clone)]
+pub struct DenseGrid {
+    /// position of the top-left cell
+    origin: (i32, i32),
+    width: usize,
+    height: usize,
+    words_per_row: usize,
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl DenseGrid {
+    pub fn new(origin: (i32, i32), width: usize, height: usize) -> Self {
+        let words_per_row = width.div_ceil(64);
+        Self {
+            origin,
+            width,
+            height,
+            words_per_row,
+            bits: vec![0; words_per_row * height],
+            len: 0,
+        }
+    }
+
+    /// Cell coordinates of `roll` relative to origin, if inside.
     #[inline]
-    fn count_neighbors(&self, roll: &Roll) -> usize {
-        roll.neighbors().filter(|n| self.rolls.contains(n)).count()
+    fn cell(&self, roll: &Roll) -> Option<(usize, usize)> {
+        rect_cell(self.origin, self.width, self.height, roll)
     }
 
-    /// SUPER-OPTIMIZED VERSION
-    fn remove_accesibles(&mut self) -> usize {
-        // neighbor count cache
-        let mut neigh = AHashMap::<Roll, u8>::new();
-        neigh.reserve(self.rolls.len());
+    /// Word index and bit mask of a cell.
+    #[inline]
+    fn slot(&self, (x, y): (usize, usize)) -> (usize, u64) {
+        (y * self.words_per_row + x / 64, 1 << (x % 64))
+    }
+
+    /// Word `w` of row `y`, zero outside the grid.
+    #[inline]
+    fn word(&self, y: isize, w: isize) -> u64 {
+        if y < 0 || w < 0 || y as usize >= self.height || w as usize >= self.words_per_row {
+            return 0;
+        }
+        self.bits[y as usize * self.words_per_row + w as usize]
+    }
+
+    /// Neighbor counts of the 64 cells in word `w` of row `y`, as 4 bit planes
+    /// (plane i holds bit i of each count).
+    fn moore_planes(&self, y: usize, w: usize) -> [u64; 4] {
+        let (y, w) = (y as isize, w as isize);
+        // bit x of west holds cell x - 1, bit x of east holds cell x + 1
+        let west = |y| (self.word(y, w) << 1) | (self.word(y, w - 1) >> 63);
+        let east = |y| (self.word(y, w) >> 1) | (self.word(y, w + 1) << 63);
+        let inputs = [
+            west(y - 1),
+            self.word(y - 1, w),
+            east(y - 1),
+            west(y),
+            east(y),
+            west(y + 1),
+            self.word(y + 1, w),
+            east(y + 1),
+        ];
+        // bit-sliced ripple adder
+        let mut planes = [0u64; 4];
+        for input in inputs {
+            let mut carry = input;
+            for plane in planes.iter_mut() {
+                let next = *plane & carry;
+                *plane ^= carry;
+                carry = next;
+            }
+        }
+        planes
+    }
+}
+
+impl RollStore for DenseGrid {
+    fn contains(&self, roll: &Roll) -> bool {
+        match self.cell(roll) {
+            Some(cell) => {
+                let (i, mask) = self.slot(cell);
+                self.bits[i] & mask != 0
+            }
+            None => false,
+        }
+    }
+    fn insert(&mut self, roll: Roll) -> bool {
+        let cell = self.cell(&roll).expect("roll outside of dense grid");
+        let (i, mask) = self.slot(cell);
+        if self.bits[i] & mask != 0 {
+            return false;
+        }
+        self.bits[i] |= mask;
+        self.len += 1;
+        true
+    }
+    fn remove(&mut self, roll: &Roll) -> bool {
+        let Some(cell) = self.cell(roll) else {
+            return false;
+        };
+        let (i, mask) = self.slot(cell);
+        if self.bits[i] & mask == 0 {
+            return false;
+        }
+        self.bits[i] &= !mask;
+        self.len -= 1;
+        true
+    }
+    fn len(&self) -> usize {
+        self.len
+    }
+    fn iter(&self) -> Box<dyn Iterator<Item = Roll> + '_> {
+        let (ox, oy) = self.origin;
+        Box::new(self.bits.iter().enumerate().flat_map(move |(i, &word)| {
+            let y = (i / self.words_per_row) as i32;
+            let base = (i % self.words_per_row * 64) as i32;
+            BitIter(word).map(move |b| Roll::new(ox + base + b as i32, oy + y))
+        }))
+    }
+    fn can_hold(&self, roll: &Roll) -> bool {
+        self.cell(roll).is_some()
+    }
+    fn is_dense(&self) -> bool {
+        true
+    }
+    fn bounds(&self) -> Option<Rect> {
+        Some(Rect { origin: self.origin, width: self.width, height: self.height })
+    }
+    fn neighbor_counts(&self, neighborhood: &Neighborhood) -> NeighborCounts {
+        if !neighborhood.is_moore() {
+            return count_neighbors(self, &neighborhood.offsets());
+        }
+        let mut counts = vec![NeighborCounts::NOT_A_ROLL; self.width * self.height];
+        for y in 0..self.height {
+            for w in 0..self.words_per_row {
+                let rolls = self.word(y as isize, w as isize);
+                if rolls == 0 {
+                    continue;
+                }
+                let planes = self.moore_planes(y, w);
+                for b in BitIter(rolls) {
+                    let count = planes
+                        .iter()
+                        .enumerate()
+                        .fold(0, |acc, (i, plane)| acc | (((plane >> b) & 1) << i));
+                    counts[y * self.width + w * 64 + b as usize] = count as u16;
+                }
+            }
+        }
+        NeighborCounts::Dense { origin: self.origin, width: self.width, height: self.height, counts }
+    }
+    fn clone_box(&self) -> Box<dyn RollStore> {
+        Box::new(self.clone())
+    }
+}
+
+/// Iterates set bit positions of a word.
+struct BitIter(u64);
+
+impl Iterator for BitIter {
+    type Item = u32;
+    fn next(&mut self) -> Option<u32> {
+        if self.0 == 0 {
+            return None;
+        }
+        let b = self.0.trailing_zeros();
+        self.0 &= self.0 - 1;
+        Some(b)
+    }
+}
+
+/// Neighbor count of each roll, laid out like the store that computed it.
+#[derive(Debug, Clone)]
+pub enum NeighborCounts {
+    Dense {
+        origin: (i32, i32),
+        width: usize,
+        height: usize,
+        /// row-major, [`NeighborCounts::NOT_A_ROLL`] for empty cells
+        counts: Vec<u16>,
+    },
+    Sparse(AHashMap<Roll, u16>),
+}
+
+impl NeighborCounts {
+    const NOT_A_ROLL: u16 = u16::MAX;
+
+    pub fn get(&self, roll: &Roll) -> Option<u16> {
+        match self {
+            NeighborCounts::Sparse(counts) => counts.get(roll).copied(),
+            NeighborCounts::Dense { origin, width, height, counts } => {
+                let i = rect_index(*origin, *width, *height, roll)?;
+                Some(counts[i]).filter(|&c| c != Self::NOT_A_ROLL)
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self, roll: &Roll) -> Option<&mut u16> {
+        match self {
+            NeighborCounts::Sparse(counts) => counts.get_mut(roll),
+            NeighborCounts::Dense { origin, width, height, counts } => {
+                let i = rect_index(*origin, *width, *height, roll)?;
+                Some(&mut counts[i]).filter(|c| **c != Self::NOT_A_ROLL)
+            }
+        }
+    }
 
-        // compute neighbor counts once
-        for r in &self.rolls {
-            let count = r.neighbors().filter(|n| self.rolls.contains(n)).count();
-            neigh.insert(*r, count as u8);
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (Roll, u16)> + '_> {
+        match self {
+            NeighborCounts::Sparse(counts) => Box::new(counts.iter().map(|(r, c)| (*r, *c))),
+            NeighborCounts::Dense { origin, width, counts, .. } => Box::new(
+                counts
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c != Self::NOT_A_ROLL)
+                    .map(move |(i, c)| {
+                        let roll = Roll::new(origin.0 + (i % width) as i32, origin.1 + (i / width) as i32);
+                        (roll, *c)
+                    }),
+            ),
         }
+    }
+}
+
+/// Cell coordinates of `roll` in a `width` x `height` rectangle starting at `origin`.
+#[inline]
+fn rect_cell(origin: (i32, i32), width: usize, height: usize, roll: &Roll) -> Option<(usize, usize)> {
+    let x = roll.pos.0 as i64 - origin.0 as i64;
+    let y = roll.pos.1 as i64 - origin.1 as i64;
+    if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+        return None;
+    }
+    Some((x as usize, y as usize))
+}
+
+/// Row-major index of `roll` in a `width` x `height` rectangle starting at `origin`.
+#[inline]
+fn rect_index(origin: (i32, i32), width: usize, height: usize, roll: &Roll) -> Option<usize> {
+    rect_cell(origin, width, height, roll).map(|(x, y)| y * width + x)
+}
+
+#[derive(Debug, Clone)]
+pub struct RollGrid {
+    store: Box<dyn RollStore>,
+    neighborhood: Neighborhood,
+    /// offsets of `neighborhood`
+    offsets: Vec<(i32, i32)>,
+    accessibility: Accessibility,
+    topology: Topology,
+    /// area the grid was read from, kept by the renderers
+    area: Option<Rect>,
+    /// counts kept up to date by [`RollGrid::insert`] and [`RollGrid::remove`], built on
+    /// first use so read-only queries can fill it, dropped by anything else that changes
+    /// the grid
+    live: OnceCell<Live>,
+}
+
+/// Neighbor counts and accessible rolls of the current grid.
+#[derive(Debug, Clone, Default)]
+struct Live {
+    counts: AHashMap<Roll, u16>,
+    accessible: AHashSet<Roll>,
+}
+
+impl RollGrid {
+    /// Builds a grid, using [`DenseGrid`] when the bounding box of `rolls` is small enough
+    /// and [`SparseGrid`] otherwise.
+    pub fn from_rolls(rolls: impl IntoIterator<Item = Roll>) -> Self {
+        let rolls: Vec<Roll> = rolls.into_iter().collect();
+        let area = Rect::enclosing(rolls.iter().copied()).unwrap_or(Rect { origin: (0, 0), width: 0, height: 0 });
+        let cells = area.width.saturating_mul(area.height);
+        // avoid mostly empty bitsets for scattered rolls
+        let mut store: Box<dyn RollStore> = if cells <= DENSE_MAX_CELLS && cells <= (64 * rolls.len()).max(4096) {
+            Box::new(DenseGrid::new(area.origin, area.width, area.height))
+        } else {
+            Box::new(SparseGrid::default())
+        };
+        for r in rolls {
+            store.insert(r);
+        }
+        Self::with_store(store)
+    }
+
+    pub fn with_store(store: Box<dyn RollStore>) -> Self {
+        let neighborhood = Neighborhood::default();
+        Self {
+            store,
+            offsets: neighborhood.offsets(),
+            neighborhood,
+            accessibility: Accessibility::default(),
+            topology: Topology::default(),
+            area: None,
+            live: OnceCell::new(),
+        }
+    }
+
+    /// Remembers the area the grid was read from, so renderers draw all of it even
+    /// where there are no rolls. [`GridParser::parse`] sets it.
+    pub fn area(mut self, area: Rect) -> Self {
+        self.area = Some(area);
+        self
+    }
+
+    /// Places the rolls on `topology`. Fails if a roll lies outside of its area.
+    pub fn topology(mut self, topology: Topology) -> anyhow::Result<Self> {
+        if let Some(r) = self.store.iter().find(|r| !topology.allows(r)) {
+            bail!("roll at {:?} is outside of {:?}", r.pos, topology);
+        }
+        if let Topology::Bounded { area, .. } | Topology::Torus(area) = topology
+            && area.width.saturating_mul(area.height) == 0
+        {
+            bail!("grid area cannot be empty");
+        }
+        self.topology = topology;
+        self.live.take();
+        Ok(self)
+    }
+
+    /// Neighbor count of every roll under the grid's neighborhood and topology.
+    fn neighbor_counts(&self) -> NeighborCounts {
+        match self.topology {
+            // no rolls outside, so only walls need adding
+            Topology::Unbounded | Topology::Bounded { walls: Walls::Empty, .. } => {
+                self.store.neighbor_counts(&self.neighborhood)
+            }
+            Topology::Bounded { walls: Walls::Occupied, .. } => {
+                let mut counts = self.store.neighbor_counts(&self.neighborhood);
+                let rolls: Vec<Roll> = counts.iter().map(|(r, _)| r).collect();
+                for r in rolls {
+                    let walls = r.neighbors(&self.offsets).filter(|n| self.topology.resolve(*n).is_none()).count();
+                    if let Some(count) = counts.get_mut(&r) {
+                        *count += walls as u16;
+                    }
+                }
+                counts
+            }
+            Topology::Torus(_) => {
+                let mut counts = AHashMap::with_capacity(self.store.len());
+                for r in self.store.iter() {
+                    let count = r
+                        .neighbors(&self.offsets)
+                        .filter_map(|n| self.topology.resolve(n))
+                        .filter(|n| *n != r && self.store.contains(n))
+                        .count();
+                    counts.insert(r, count as u16);
+                }
+                NeighborCounts::Sparse(counts)
+            }
+        }
+    }
+
+    /// Cells whose neighbor count includes `roll`.
+    #[inline]
+    fn dependents<'a>(&'a self, roll: &Roll) -> impl Iterator<Item = Roll> + 'a {
+        roll.dependents(&self.offsets).filter_map(|p| self.topology.resolve(p))
+    }
 
-        // queue of rolls currently <4 neighbors
+    /// Uses `neighborhood` instead of the 8 surrounding cells.
+    pub fn neighborhood(mut self, neighborhood: Neighborhood) -> Self {
+        self.offsets = neighborhood.offsets();
+        self.neighborhood = neighborhood;
+        self.live.take();
+        self
+    }
+
+    /// Uses `accessibility` instead of fewer than 4 neighbors.
+    pub fn accessibility(mut self, accessibility: Accessibility) -> Self {
+        self.accessibility = accessibility;
+        self.live.take();
+        self
+    }
+
+    pub fn store(&self) -> &dyn RollStore {
+        self.store.as_ref()
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    pub fn accessible_rolls(&self) -> usize {
+        self.neighbor_counts()
+            .iter()
+            .filter(|(_, c)| self.accessibility.accepts(*c))
+            .count()
+    }
+
+    /// Removes accessible rolls until none is left, returns the number removed.
+    /// With a rule that is not "less than", a roll can also become inaccessible as its
+    /// neighbors go, so the outcome depends on removal order.
+    ///
+    /// SUPER-OPTIMIZED VERSION
+    pub fn remove_accesibles(&mut self) -> usize {
+        self.live.take();
+        // neighbor count cache, computed once
+        let mut neigh = self.neighbor_counts();
+
+        // queue of rolls currently accessible
         let mut q = VecDeque::new();
         for (r, c) in neigh.iter() {
-            if *c < 4 {
-                q.push_back(*r);
+            if self.accessibility.accepts(c) {
+                q.push_back(r);
             }
         }
 
@@ -77,19 +727,20 @@ impl RollGrid {
 
         // cascading removals
         while let Some(r) = q.pop_front() {
-            if !self.rolls.remove(&r) {
-                continue; // already removed
+            // already removed, or no longer accessible
+            if neigh.get(&r).is_none_or(|c| !self.accessibility.accepts(c)) || !self.store.remove(&r) {
+                continue;
             }
             removed += 1;
 
-            // update neighbors
-            for adj in r.neighbors() {
-                if let Some(count) = neigh.get_mut(&adj) {
-                    if *count > 0 {
-                        *count -= 1;
-                        if *count < 4 {
-                            q.push_back(adj);
-                        }
+            // update rolls that counted it
+            for adj in self.dependents(&r) {
+                if self.store.contains(&adj)
+                    && let Some(count) = neigh.get_mut(&adj)
+                {
+                    *count -= 1;
+                    if self.accessibility.accepts(*count) {
+                        q.push_back(adj);
                     }
                 }
             }
@@ -97,25 +748,1216 @@ impl RollGrid {
 
         removed
     }
+
+    /// Removes accessible rolls in synchronous waves: every roll accessible at the start
+    /// of a wave goes at once. Stops when a wave would remove nothing.
+    pub fn remove_in_waves(&mut self) -> Timeline {
+        self.live.take();
+        let mut neigh = self.neighbor_counts();
+        let mut frontier: Vec<Roll> = neigh
+            .iter()
+            .filter(|(_, c)| self.accessibility.accepts(*c))
+            .map(|(r, _)| r)
+            .collect();
+
+        let mut timeline = Timeline::default();
+        while !frontier.is_empty() {
+            for r in &frontier {
+                self.store.remove(r);
+            }
+            // only rolls around the removed ones can change status
+            let mut touched = AHashSet::new();
+            for r in &frontier {
+                for adj in self.dependents(r) {
+                    if self.store.contains(&adj)
+                        && let Some(count) = neigh.get_mut(&adj)
+                    {
+                        *count -= 1;
+                        touched.insert(adj);
+                    }
+                }
+            }
+            frontier.sort_by_key(|r| (r.pos.1, r.pos.0));
+            timeline.waves.push(frontier);
+            frontier = touched
+                .into_iter()
+                .filter(|r| neigh.get(r).is_some_and(|c| self.accessibility.accepts(c)))
+                .collect();
+        }
+        timeline
+    }
+}
+
+impl RollGrid {
+    /// Neighbor count of a single cell under the grid's neighborhood and topology.
+    fn count_at(&self, pos: &Roll) -> u16 {
+        let walls_count = matches!(self.topology, Topology::Bounded { walls: Walls::Occupied, .. });
+        pos.neighbors(&self.offsets)
+            .filter(|n| match self.topology.resolve(*n) {
+                Some(n) => n != *pos && self.store.contains(&n),
+                None => walls_count,
+            })
+            .count() as u16
+    }
+
+    /// Live counts, built on first use.
+    fn live(&self) -> &Live {
+        self.live.get_or_init(|| {
+            let mut live = Live::default();
+            for (r, c) in self.neighbor_counts().iter() {
+                live.counts.insert(r, c);
+                if self.accessibility.accepts(c) {
+                    live.accessible.insert(r);
+                }
+            }
+            live
+        })
+    }
+
+    fn live_mut(&mut self) -> &mut Live {
+        self.live();
+        self.live.get_mut().expect("live counts were just built")
+    }
+
+    /// Adds `count_delta` to the count of every other roll that has `roll` as a neighbor.
+    fn update_dependents(&mut self, roll: &Roll, count_delta: i32) {
+        let dependents: Vec<Roll> = self
+            .dependents(roll)
+            .filter(|d| d != roll && self.store.contains(d))
+            .collect();
+        let accessibility = self.accessibility;
+        let live = self.live_mut();
+        for d in dependents {
+            let count = live.counts.entry(d).or_default();
+            *count = (*count as i32 + count_delta) as u16;
+            if accessibility.accepts(*count) {
+                live.accessible.insert(d);
+            } else {
+                live.accessible.remove(&d);
+            }
+        }
+    }
+
+    /// Adds a roll, keeping neighbor counts and accessible rolls up to date in
+    /// O(neighborhood size). Returns false if there already was one.
+    /// Fails if the topology has no such cell. A roll outside a dense store's area moves
+    /// the grid to a sparse store for good.
+    pub fn insert(&mut self, roll: Roll) -> anyhow::Result<bool> {
+        if !self.topology.allows(&roll) {
+            bail!("roll at {:?} is outside of {:?}", roll.pos, self.topology);
+        }
+        self.live();
+        if !self.store.can_hold(&roll) {
+            // grew out of the dense area
+            let mut sparse = SparseGrid::default();
+            for r in self.store.iter() {
+                sparse.insert(r);
+            }
+            self.store = Box::new(sparse);
+        }
+        if !self.store.insert(roll) {
+            return Ok(false);
+        }
+        let count = self.count_at(&roll);
+        let accessible = self.accessibility.accepts(count);
+        let live = self.live_mut();
+        live.counts.insert(roll, count);
+        if accessible {
+            live.accessible.insert(roll);
+        }
+        self.update_dependents(&roll, 1);
+        Ok(true)
+    }
+
+    /// Removes a roll, keeping neighbor counts and accessible rolls up to date.
+    /// Returns false if there was none.
+    pub fn remove(&mut self, roll: &Roll) -> bool {
+        self.live();
+        if !self.store.remove(roll) {
+            return false;
+        }
+        let live = self.live_mut();
+        live.counts.remove(roll);
+        live.accessible.remove(roll);
+        self.update_dependents(roll, -1);
+        true
+    }
+
+    /// Number of accessible rolls, kept up to date by [`RollGrid::insert`] and [`RollGrid::remove`].
+    pub fn accessible_count(&self) -> usize {
+        self.live().accessible.len()
+    }
+
+    pub fn is_accessible(&self, roll: &Roll) -> bool {
+        self.live().accessible.contains(roll)
+    }
+
+    /// Neighbor count of `roll`, `None` if there is no such roll.
+    pub fn neighbor_count(&self, roll: &Roll) -> Option<u16> {
+        self.live().counts.get(roll).copied()
+    }
+
+    /// Number of accessible rolls if a roll were added at `pos`, worked out from the live
+    /// counts in O(neighborhood size) without touching the grid or its store.
+    pub fn accessible_if_inserted(&self, pos: Roll) -> anyhow::Result<usize> {
+        if !self.topology.allows(&pos) {
+            bail!("roll at {:?} is outside of {:?}", pos.pos, self.topology);
+        }
+        let live = self.live();
+        if self.store.contains(&pos) {
+            return Ok(live.accessible.len());
+        }
+        let mut accessible = live.accessible.len();
+        if self.accessibility.accepts(self.count_at(&pos)) {
+            accessible += 1;
+        }
+        let mut gains: AHashMap<Roll, u16> = AHashMap::new();
+        for d in self.dependents(&pos).filter(|d| *d != pos && self.store.contains(d)) {
+            *gains.entry(d).or_default() += 1;
+        }
+        for (d, gain) in gains {
+            let before = live.counts[&d];
+            match (self.accessibility.accepts(before), self.accessibility.accepts(before + gain)) {
+                (true, false) => accessible -= 1,
+                (false, true) => accessible += 1,
+                _ => {}
+            }
+        }
+        Ok(accessible)
+    }
+}
+
+/// Rolls to add so that no roll is accessible, see [`RollGrid::reinforce`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reinforcement {
+    /// in the order they were chosen
+    pub added: Vec<Roll>,
+    /// whether `added` is known to be as small as possible
+    pub exact: bool,
+}
+
+impl RollGrid {
+    /// Finds empty cells to fill so that no roll, old or new, is accessible. Searches for
+    /// a smallest set on small grids and falls back to a greedy choice on large ones.
+    /// Cells are taken from the topology's area, or on an unbounded plane from the bounds
+    /// grown by as far as the neighborhood reaches on each axis. Only "less than" rules are supported, since for them adding
+    /// rolls can never make a roll accessible. Fails if no filling of the area works.
+    pub fn reinforce(&self) -> anyhow::Result<Reinforcement> {
+        self.reinforce_with_budget(EXACT_NODE_BUDGET)
+    }
+
+    fn reinforce_with_budget(&self, mut budget: usize) -> anyhow::Result<Reinforcement> {
+        let need = match self.accessibility.comparison {
+            Comparison::Less => Some(self.accessibility.threshold),
+            Comparison::LessEq => self.accessibility.threshold.checked_add(1),
+            _ => bail!("cannot reinforce against {:?}", self.accessibility),
+        };
+        let Some(need) = need else {
+            bail!("no roll can have more than {} neighbors, {:?} is unreachable", u16::MAX, self.accessibility);
+        };
+        let area = match self.topology {
+            Topology::Bounded { area, .. } | Topology::Torus(area) => area,
+            Topology::Unbounded => match self.bounds() {
+                Some(b) => {
+                    let (rx, ry) = self.offsets.iter().fold((0, 0), |(rx, ry), (dx, dy)| {
+                        (rx.max(dx.unsigned_abs()), ry.max(dy.unsigned_abs()))
+                    });
+                    Rect {
+                        origin: (b.origin.0 - rx as i32, b.origin.1 - ry as i32),
+                        width: b.width + 2 * rx as usize,
+                        height: b.height + 2 * ry as usize,
+                    }
+                }
+                None => return Ok(Reinforcement { added: Vec::new(), exact: true }),
+            },
+        };
+        let mut grid = self.clone();
+        let empty = area.cells().filter(|c| !grid.store.contains(c)).count();
+        if empty <= EXACT_MAX_CANDIDATES {
+            // neighbors one added roll can give, more than one on a small torus
+            let origin = Roll::new(area.origin.0, area.origin.1);
+            let mut targets: Vec<Roll> = origin
+                .neighbors(&self.offsets)
+                .filter_map(|n| self.topology.resolve(n))
+                .filter(|n| *n != origin)
+                .collect();
+            targets.sort_by_key(|r| (r.pos.1, r.pos.0));
+            let gain = targets.chunk_by(|a, b| a == b).map(|c| c.len()).max().unwrap_or(1);
+
+            for depth in 0..=empty {
+                let mut added = Vec::new();
+                match grid.search_reinforcement(&area, need, gain, depth, &mut added, &mut budget) {
+                    Some(true) => return Ok(Reinforcement { added, exact: true }),
+                    Some(false) => continue,
+                    None => break, // out of budget
+                }
+            }
+            if budget > 0 {
+                bail!("no filling of {:?} leaves every roll inaccessible", area);
+            }
+        }
+        grid.greedy_reinforcement(&area, need)
+    }
+
+    /// How many more neighbors `roll` needs.
+    fn deficit(&self, roll: &Roll, need: u16) -> u16 {
+        need.saturating_sub(self.neighbor_count(roll).unwrap_or(0))
+    }
+
+    /// Empty cells of `area` that would count as a neighbor of `roll`.
+    fn fillable_around(&self, roll: &Roll, area: &Rect) -> Vec<Roll> {
+        let mut cells: Vec<Roll> = roll
+            .neighbors(&self.offsets)
+            .filter_map(|n| self.topology.resolve(n))
+            .filter(|c| area.contains(c) && !self.store.contains(c))
+            .collect();
+        cells.sort_by_key(|r| (r.pos.1, r.pos.0));
+        cells.dedup();
+        cells
+    }
+
+    /// Depth-limited search: some neighbor of an accessible roll must be filled, so try
+    /// each around the neediest one. `None` if the node budget ran out. Unless a filling is
+    /// found, the grid and `added` are left as they were.
+    fn search_reinforcement(
+        &mut self,
+        area: &Rect,
+        need: u16,
+        gain: usize,
+        depth: usize,
+        added: &mut Vec<Roll>,
+        budget: &mut usize,
+    ) -> Option<bool> {
+        if *budget == 0 {
+            return None;
+        }
+        *budget -= 1;
+        let accessible: Vec<Roll> = self.live().accessible.iter().copied().collect();
+        let Some(neediest) = accessible.into_iter().max_by_key(|r| (self.deficit(r, need), r.pos.1, r.pos.0)) else {
+            return Some(true);
+        };
+        // each added roll gives the neediest one at most `gain` more neighbors
+        if self.deficit(&neediest, need) as usize > depth * gain {
+            return Some(false);
+        }
+        for cell in self.fillable_around(&neediest, area) {
+            self.insert(cell).ok()?;
+            added.push(cell);
+            let found = self.search_reinforcement(area, need, gain, depth - 1, added, budget);
+            if found == Some(true) {
+                return found;
+            }
+            // undone also when out of budget, the greedy fallback starts from this grid
+            added.pop();
+            self.remove(&cell);
+            found?;
+        }
+        Some(false)
+    }
+
+    /// Repeatedly fills the cell around an accessible roll that removes the most missing
+    /// neighbors, counting what the new roll itself lacks.
+    fn greedy_reinforcement(&mut self, area: &Rect, need: u16) -> anyhow::Result<Reinforcement> {
+        let mut added = Vec::new();
+        loop {
+            let accessible: Vec<Roll> = self.live().accessible.iter().copied().collect();
+            if accessible.is_empty() {
+                return Ok(Reinforcement { added, exact: false });
+            }
+            let mut candidates: Vec<Roll> = accessible.iter().flat_map(|r| self.fillable_around(r, area)).collect();
+            candidates.sort_by_key(|r| (r.pos.1, r.pos.0));
+            candidates.dedup();
+
+            let mut best: Option<(i64, Roll)> = None;
+            for cell in candidates {
+                let helped = self
+                    .dependents(&cell)
+                    .filter(|d| *d != cell && accessible.contains(d))
+                    .count() as i64;
+                let lacking = need.saturating_sub(self.count_at(&cell)) as i64;
+                let score = helped - lacking;
+                if best.is_none_or(|(b, _)| score > b) {
+                    best = Some((score, cell));
+                }
+            }
+            let Some((_, cell)) = best else {
+                bail!("no filling of {:?} leaves every roll inaccessible", area);
+            };
+            self.insert(cell)?;
+            added.push(cell);
+        }
+    }
+}
+
+/// How a cell is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Cell {
+    Empty,
+    Roll,
+    Highlighted,
+}
+
+impl Cell {
+    fn glyph(self) -> char {
+        match self {
+            Cell::Empty => '.',
+            Cell::Roll => '@',
+            Cell::Highlighted => 'x',
+        }
+    }
+
+    fn rgb(self) -> [u8; 3] {
+        match self {
+            Cell::Empty => [240, 240, 240],
+            Cell::Roll => [60, 60, 60],
+            Cell::Highlighted => [220, 40, 40],
+        }
+    }
+}
+
+impl RollGrid {
+    /// Area the rolls are in, see [`RollStore::bounds`].
+    pub fn bounds(&self) -> Option<Rect> {
+        self.store.bounds()
+    }
+
+    /// What the renderers draw: the input area, else the topology's area, grown to the
+    /// bounds of the rolls if any lie outside.
+    fn drawn_area(&self) -> Option<Rect> {
+        let area = self.area.or(match self.topology {
+            Topology::Bounded { area, .. } | Topology::Torus(area) => Some(area),
+            Topology::Unbounded => None,
+        });
+        match (area, self.bounds()) {
+            (Some(area), Some(bounds)) => Some(area.union(&bounds)),
+            (area, bounds) => area.or(bounds).filter(|r| r.width > 0 && r.height > 0),
+        }
+    }
+
+    /// Rolls that are currently accessible, in row-major order.
+    pub fn accessible(&self) -> Vec<Roll> {
+        let mut rolls: Vec<Roll> = self
+            .neighbor_counts()
+            .iter()
+            .filter(|(_, c)| self.accessibility.accepts(*c))
+            .map(|(r, _)| r)
+            .collect();
+        rolls.sort_by_key(|r| (r.pos.1, r.pos.0));
+        rolls
+    }
+
+    fn cell(&self, pos: &Roll, highlight: &AHashSet<Roll>) -> Cell {
+        if !self.store.contains(pos) {
+            Cell::Empty
+        } else if highlight.contains(pos) {
+            Cell::Highlighted
+        } else {
+            Cell::Roll
+        }
+    }
+
+    /// Draws the grid in the input format, with accessible rolls as `x`.
+    pub fn render(&self) -> String {
+        let Some(area) = self.drawn_area() else {
+            return String::new();
+        };
+        let highlight = self.accessible().into_iter().collect();
+        self.render_area(area, &highlight)
+    }
+
+    /// Draws `area` in the input format, with rolls in `highlight` as `x`.
+    pub fn render_area(&self, area: Rect, highlight: &AHashSet<Roll>) -> String {
+        let mut out = String::with_capacity((area.width + 1) * area.height);
+        for (i, pos) in area.cells().enumerate() {
+            if i > 0 && i % area.width == 0 {
+                out.push('\n');
+            }
+            out.push(self.cell(&pos, highlight).glyph());
+        }
+        out
+    }
+
+    /// Writes `area` as a binary PPM image, each cell `scale` pixels wide,
+    /// with rolls in `highlight` in red.
+    pub fn write_ppm<W: Write>(&self, mut out: W, area: Rect, highlight: &AHashSet<Roll>, scale: usize) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", area.width * scale, area.height * scale)?;
+        let (ox, oy) = area.origin;
+        let mut row = Vec::with_capacity(area.width * scale * 3);
+        for y in 0..area.height as i32 {
+            row.clear();
+            for x in 0..area.width as i32 {
+                let rgb = self.cell(&Roll::new(ox + x, oy + y), highlight).rgb();
+                for _ in 0..scale {
+                    row.extend_from_slice(&rgb);
+                }
+            }
+            for _ in 0..scale {
+                out.write_all(&row)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes one PPM frame per removal wave into `dir` (`wave_000.ppm`, ...), the rolls
+    /// about to be removed highlighted, plus a last frame of the stable grid.
+    /// Returns the number of frames written.
+    pub fn write_wave_frames(&self, dir: &Path, scale: usize) -> anyhow::Result<usize> {
+        let Some(area) = self.drawn_area() else {
+            return Ok(0);
+        };
+        std::fs::create_dir_all(dir)?;
+        let timeline = self.clone().remove_in_waves();
+        let mut grid = self.clone();
+        grid.live.take();
+        let mut frames = 0;
+        let mut write_frame = |grid: &RollGrid, highlight: &AHashSet<Roll>| -> anyhow::Result<()> {
+            let file = std::fs::File::create(dir.join(format!("wave_{:03}.ppm", frames)))?;
+            grid.write_ppm(io::BufWriter::new(file), area, highlight, scale)?;
+            frames += 1;
+            Ok(())
+        };
+        for wave in &timeline.waves {
+            write_frame(&grid, &wave.iter().copied().collect())?;
+            for r in wave {
+                grid.store.remove(r);
+            }
+        }
+        write_frame(&grid, &AHashSet::new())?;
+        Ok(frames)
+    }
+}
+
+/// Group of 8-connected rolls.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// in row-major order
+    pub rolls: Vec<Roll>,
+    pub bounds: Rect,
+}
+
+impl Component {
+    pub fn size(&self) -> usize {
+        self.rolls.len()
+    }
+}
+
+/// A component of the initial grid and how many of its rolls outlive the cascade.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentSurvival {
+    pub component: Component,
+    pub surviving: usize,
+}
+
+impl ComponentSurvival {
+    pub fn survives(&self) -> bool {
+        self.surviving > 0
+    }
+}
+
+impl RollGrid {
+    /// 8-connected components (wrapping around on a torus), ordered by their first roll
+    /// in row-major order.
+    pub fn components(&self) -> Vec<Component> {
+        let moore = Neighborhood::Moore.offsets();
+        let mut seen = AHashSet::with_capacity(self.store.len());
+        let mut rolls: Vec<Roll> = self.store.iter().collect();
+        rolls.sort_by_key(|r| (r.pos.1, r.pos.0));
+
+        let mut components = Vec::new();
+        let mut stack = Vec::new();
+        for start in rolls {
+            if !seen.insert(start) {
+                continue;
+            }
+            let mut members = vec![start];
+            stack.push(start);
+            while let Some(r) = stack.pop() {
+                for n in r.neighbors(&moore).filter_map(|n| self.topology.resolve(n)) {
+                    if self.store.contains(&n) && seen.insert(n) {
+                        members.push(n);
+                        stack.push(n);
+                    }
+                }
+            }
+            members.sort_by_key(|r| (r.pos.1, r.pos.0));
+            let bounds = Rect::enclosing(members.iter().copied()).expect("a component has a roll");
+            components.push(Component { rolls: members, bounds });
+        }
+        components
+    }
+
+    /// Components of the grid with the number of their rolls left after
+    /// [`RollGrid::remove_accesibles`]. The grid itself is not changed.
+    pub fn component_survival(&self) -> Vec<ComponentSurvival> {
+        let mut after = self.clone();
+        after.remove_accesibles();
+        self.components()
+            .into_iter()
+            .map(|component| {
+                let surviving = component.rolls.iter().filter(|r| after.store.contains(r)).count();
+                ComponentSurvival { component, surviving }
+            })
+            .collect()
+    }
+}
+
+/// Rolls removed by [`RollGrid::remove_in_waves`], one entry per wave.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Timeline {
+    /// removed rolls of each wave, in row-major order
+    pub waves: Vec<Vec<Roll>>,
+}
+
+impl Timeline {
+    /// Number of waves until the grid was stable.
+    pub fn wave_count(&self) -> usize {
+        self.waves.len()
+    }
+
+    /// Number of rolls removed by each wave.
+    pub fn removed_per_wave(&self) -> Vec<usize> {
+        self.waves.iter().map(|w| w.len()).collect()
+    }
+
+    pub fn total_removed(&self) -> usize {
+        self.waves.iter().map(|w| w.len()).sum()
+    }
+}
+
+/// Parses a grid of roll and empty glyphs. By default `@` is a roll, `.` is empty and
+/// all rows must have the same width.
+#[derive(Debug, Clone)]
+pub struct GridParser {
+    roll_glyphs: Vec<char>,
+    empty_glyphs: Vec<char>,
+    /// whether rows may differ in width, missing cells being empty
+    allow_ragged: bool,
+}
+
+impl Default for GridParser {
+    fn default() -> Self {
+        Self { roll_glyphs: vec!['@'], empty_glyphs: vec!['.'], allow_ragged: false }
+    }
+}
+
+impl GridParser {
+    /// Characters that mark a roll.
+    pub fn roll_glyphs(mut self, glyphs: &str) -> Self {
+        self.roll_glyphs = glyphs.chars().collect();
+        self
+    }
+
+    /// Characters that mark an empty cell.
+    pub fn empty_glyphs(mut self, glyphs: &str) -> Self {
+        self.empty_glyphs = glyphs.chars().collect();
+        self
+    }
+
+    /// Accepts rows of different widths, treating missing cells as empty.
+    pub fn allow_ragged(mut self, allow: bool) -> Self {
+        self.allow_ragged = allow;
+        self
+    }
+
+    /// Parses `content`, returning the grid and the area the input covers.
+    /// Handles `\n` and `\r\n` line endings and ignores trailing blank lines.
+    pub fn parse(&self, content: &str) -> anyhow::Result<(RollGrid, Rect)> {
+        if let Some(c) = self.roll_glyphs.iter().find(|c| self.empty_glyphs.contains(c)) {
+            bail!("glyph {:?} cannot mark both a roll and an empty cell", c);
+        }
+        let lines: Vec<&str> = content.lines().map(|l| l.trim_end_matches('\r')).collect();
+        let height = lines.iter().rposition(|l| !l.is_empty()).map_or(0, |i| i + 1);
+
+        let mut rolls = Vec::new();
+        let mut width = None;
+        for (y, line) in lines[..height].iter().enumerate() {
+            let mut row_width = 0;
+            for (x, c) in line.chars().enumerate() {
+                if self.roll_glyphs.contains(&c) {
+                    rolls.push(Roll { pos: (x as i32, y as i32) });
+                } else if !self.empty_glyphs.contains(&c) {
+                    bail!("unexpected character {:?} at line {}, column {}", c, y + 1, x + 1);
+                }
+                row_width += 1;
+            }
+            match width {
+                None => width = Some(row_width),
+                Some(w) if w != row_width && !self.allow_ragged => {
+                    bail!("line {} has {} cells, expected {}", y + 1, row_width, w)
+                }
+                Some(w) => width = Some(w.max(row_width)),
+            }
+        }
+        let area = Rect { origin: (0, 0), width: width.unwrap_or(0), height };
+        Ok((RollGrid::from_rolls(rolls).area(area), area))
+    }
 }
 
 fn read_input() -> anyhow::Result<RollGrid> {
     let content = std::fs::read_to_string("inputs/input4.txt")?;
-    let mut rolls = AHashSet::new();
-    let mut y = 0;
-    for line in content.lines() {
-        let mut x = 0;
-        for c in line.chars() {
-            match c {
-                '@' => {
-                    rolls.insert(Roll { pos: (x, y) });
+    let (grid, _) = GridParser::default().parse(&content)?;
+    Ok(grid)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = "\
+..@@.@@@@.
+@@@.@.@.@@
+@@@@@.@.@@
+@.@@@@..@.
+@@.@@@@.@@
+.@@@@@@@.@
+.@.@.@.@@@
+@.@@@.@@@@
+.@@@@@@@@.
+@.@.@@@.@.";
+
+    fn example_rolls() -> Vec<Roll> {
+        let mut rolls = Vec::new();
+        for (y, line) in EXAMPLE.lines().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                if c == '@' {
+                    rolls.push(Roll::new(x as i32, y as i32));
                 }
-                '.' => {}
-                x => bail!("Unexpected character in input: {}", x),
             }
-            x += 1;
         }
-        y += 1;
+        rolls
+    }
+
+    fn sparse(rolls: &[Roll]) -> RollGrid {
+        let mut store = SparseGrid::default();
+        for r in rolls {
+            store.insert(*r);
+        }
+        RollGrid::with_store(Box::new(store))
+    }
+
+    #[test]
+    fn test_example() {
+        let grid = RollGrid::from_rolls(example_rolls());
+        assert!(grid.store().is_dense());
+        assert_eq!(grid.accessible_rolls(), 13);
+        let mut grid = grid;
+        assert_eq!(grid.remove_accesibles(), 43);
+
+        let mut grid = sparse(&example_rolls());
+        assert_eq!(grid.accessible_rolls(), 13);
+        assert_eq!(grid.remove_accesibles(), 43);
+    }
+
+    #[test]
+    fn test_dense_matches_sparse() {
+        // wide enough to cross word boundaries, offset to exercise the origin
+        let rolls: Vec<Roll> = (0..3000)
+            .map(|i: i32| Roll::new((i * 7919) % 150 - 20, (i * 104729) % 30 - 5))
+            .collect();
+        let dense = RollGrid::from_rolls(rolls.clone());
+        assert!(dense.store().is_dense());
+        let sparse = sparse(&rolls);
+        assert_eq!(dense.len(), sparse.len());
+
+        let dense_counts = dense.store().neighbor_counts(&Neighborhood::Moore);
+        let sparse_counts = sparse.store().neighbor_counts(&Neighborhood::Moore);
+        for r in sparse.store().iter() {
+            assert_eq!(dense_counts.get(&r), sparse_counts.get(&r), "roll: {:?}", r);
+        }
+        assert_eq!(dense_counts.iter().count(), sparse.len());
+        assert_eq!(dense.accessible_rolls(), sparse.accessible_rolls());
+        assert_eq!(dense.clone().remove_accesibles(), sparse.clone().remove_accesibles());
+    }
+
+    #[test]
+    fn test_scattered_rolls_are_sparse() {
+        let grid = RollGrid::from_rolls([Roll::new(0, 0), Roll::new(1_000_000, -1_000_000)]);
+        assert!(!grid.store().is_dense());
+        assert_eq!(grid.accessible_rolls(), 2);
+    }
+
+    /// Removes all accessible rolls at once until none is left.
+    fn remove_in_rounds(rolls: &[Roll], offsets: &[(i32, i32)], rule: Accessibility) -> usize {
+        let mut rolls: AHashSet<Roll> = rolls.iter().copied().collect();
+        let initial = rolls.len();
+        loop {
+            let accessible: Vec<Roll> = rolls
+                .iter()
+                .filter(|r| {
+                    let count = r.neighbors(offsets).filter(|n| rolls.contains(n)).count();
+                    rule.accepts(count as u16)
+                })
+                .copied()
+                .collect();
+            if accessible.is_empty() {
+                return initial - rolls.len();
+            }
+            for r in accessible {
+                rolls.remove(&r);
+            }
+        }
+    }
+
+    #[test]
+    fn test_neighborhoods() {
+        let neighborhoods = [
+            Neighborhood::Moore,
+            Neighborhood::VonNeumann,
+            Neighborhood::Radius(2),
+            Neighborhood::Hex,
+            Neighborhood::Offsets(vec![(0, 0), (1, 0), (2, 0), (1, 0), (0, 1)]),
+        ];
+        let rules = [
+            Accessibility::default(),
+            Accessibility::new(Comparison::LessEq, 1),
+            Accessibility::new(Comparison::Less, 10),
+        ];
+        for neighborhood in &neighborhoods {
+            for rule in rules {
+                let offsets = neighborhood.offsets();
+                let expected = remove_in_rounds(&example_rolls(), &offsets, rule);
+                for mut grid in [RollGrid::from_rolls(example_rolls()), sparse(&example_rolls())] {
+                    grid = grid.neighborhood(neighborhood.clone()).accessibility(rule);
+                    assert_eq!(grid.remove_accesibles(), expected, "{:?} {:?}", neighborhood, rule);
+                }
+            }
+        }
+        assert_eq!(Neighborhood::Radius(2).offsets().len(), 24);
+        assert_eq!(Neighborhood::Offsets(vec![(0, 0), (1, 0), (1, 0)]).offsets(), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn test_non_monotone_rule() {
+        // a 2x2 block: every roll has exactly 3 neighbors
+        let block = [Roll::new(0, 0), Roll::new(1, 0), Roll::new(0, 1), Roll::new(1, 1)];
+        let grid = RollGrid::from_rolls(block).accessibility(Accessibility::new(Comparison::Equal, 3));
+        assert_eq!(grid.accessible_rolls(), 4);
+        // after the first removal the others drop to 2 neighbors
+        assert_eq!(grid.clone().remove_accesibles(), 1);
+
+        let grid = grid.accessibility(Accessibility::new(Comparison::GreaterEq, 2));
+        // removals stop once the last two rolls have a single neighbor
+        assert_eq!(grid.clone().remove_accesibles(), 2);
+    }
+
+    #[test]
+    fn test_waves() {
+        for mut grid in [RollGrid::from_rolls(example_rolls()), sparse(&example_rolls())] {
+            let timeline = grid.remove_in_waves();
+            assert_eq!(timeline.removed_per_wave(), vec![13, 12, 7, 5, 2, 1, 1, 1, 1]);
+            assert_eq!(timeline.wave_count(), 9);
+            assert_eq!(timeline.total_removed(), 43);
+            assert_eq!(timeline.waves[0][..3], [Roll::new(2, 0), Roll::new(3, 0), Roll::new(5, 0)]);
+            assert_eq!(grid.len(), example_rolls().len() - 43);
+            assert_eq!(grid.remove_in_waves(), Timeline::default());
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let grid = RollGrid::from_rolls(example_rolls());
+        let expected = "\
+..xx.xx@x.
+x@@.@.@.@@
+@@@@@.x.@@
+@.@@@@..@.
+x@.@@@@.@x
+.@@@@@@@.@
+.@.@.@.@@@
+x.@@@.@@@@
+.@@@@@@@@.
+x.x.@@@.x.";
+        assert_eq!(grid.render(), expected);
+        assert_eq!(sparse(&example_rolls()).render(), expected);
+        let area = grid.bounds().unwrap();
+        assert_eq!(grid.render_area(area, &AHashSet::new()), EXAMPLE);
+        assert_eq!(RollGrid::from_rolls([]).render(), "");
+
+        // empty border rows and columns come back as they were read
+        let area = Rect { origin: (0, 0), width: 4, height: 4 };
+        let grid = RollGrid::from_rolls([Roll::new(1, 1), Roll::new(2, 1), Roll::new(1, 2)]).area(area);
+        assert_eq!(grid.render(), "....\n.xx.\n.x..\n....");
+        let grid = RollGrid::from_rolls([]).area(Rect { origin: (0, 0), width: 3, height: 2 });
+        assert_eq!(grid.render(), "...\n...");
+        // rolls outside the input area are still drawn
+        let area = Rect { origin: (0, 0), width: 2, height: 2 };
+        let grid = RollGrid::from_rolls([Roll::new(1, 0), Roll::new(2, 0)]).area(area);
+        assert_eq!(grid.render(), ".xx\n...");
+    }
+
+    #[test]
+    fn test_ppm_frames() {
+        let grid = RollGrid::from_rolls(example_rolls());
+        let mut ppm = Vec::new();
+        grid.write_ppm(&mut ppm, grid.bounds().unwrap(), &AHashSet::new(), 2).unwrap();
+        let header = b"P6\n20 20\n255\n";
+        assert_eq!(&ppm[..header.len()], header);
+        assert_eq!(ppm.len(), header.len() + 20 * 20 * 3);
+        // top-left cell is empty
+        assert_eq!(ppm[header.len()..header.len() + 3], Cell::Empty.rgb());
+
+        let dir = std::env::temp_dir().join(format!("aoc25_day4_frames_{}", std::process::id()));
+        assert_eq!(grid.write_wave_frames(&dir, 1).unwrap(), 10);
+        assert!(dir.join("wave_009.ppm").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // frames cover the whole input, empty border column included
+        let rolls = [Roll::new(0, 0), Roll::new(1, 0), Roll::new(0, 1), Roll::new(1, 1)];
+        let grid = RollGrid::from_rolls(rolls).area(Rect { origin: (0, 0), width: 4, height: 2 });
+        assert_eq!(grid.write_wave_frames(&dir, 1).unwrap(), 2);
+        let frame = std::fs::read(dir.join("wave_000.ppm")).unwrap();
+        assert!(frame.starts_with(b"P6\n4 2\n255\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_bounded_walls() {
+        let area = Rect { origin: (0, 0), width: 3, height: 3 };
+        let grid = RollGrid::from_rolls([Roll::new(0, 0), Roll::new(1, 1)]);
+        let empty = grid.clone().topology(Topology::Bounded { area, walls: Walls::Empty }).unwrap();
+        assert_eq!(empty.accessible(), vec![Roll::new(0, 0), Roll::new(1, 1)]);
+
+        // the corner sees 5 wall cells
+        let walled = grid.topology(Topology::Bounded { area, walls: Walls::Occupied }).unwrap();
+        assert_eq!(walled.accessible(), vec![Roll::new(1, 1)]);
+        let mut cascade = walled.clone();
+        assert_eq!(cascade.remove_accesibles(), 1);
+        assert_eq!(cascade.store().iter().collect::<Vec<_>>(), vec![Roll::new(0, 0)]);
+
+        let mut example = RollGrid::from_rolls(example_rolls())
+            .topology(Topology::Bounded { area: Rect { origin: (0, 0), width: 10, height: 10 }, walls: Walls::Occupied })
+            .unwrap();
+        assert!(example.remove_accesibles() < 43);
+
+        assert!(RollGrid::from_rolls([Roll::new(3, 0)]).topology(Topology::Torus(area)).is_err());
+    }
+
+    #[test]
+    fn test_torus() {
+        let (w, h) = (10, 10);
+        // brute force rounds on the wrapped example
+        let mut rolls: AHashSet<Roll> = example_rolls().into_iter().collect();
+        let initial = rolls.len();
+        loop {
+            let accessible: Vec<Roll> = rolls
+                .iter()
+                .filter(|r| {
+                    let count = Neighborhood::Moore
+                        .offsets()
+                        .iter()
+                        .filter(|(dx, dy)| {
+                            let n = Roll::new((r.pos.0 + dx).rem_euclid(w), (r.pos.1 + dy).rem_euclid(h));
+                            rolls.contains(&n)
+                        })
+                        .count();
+                    count < 4
+                })
+                .copied()
+                .collect();
+            if accessible.is_empty() {
+                break;
+            }
+            for r in accessible {
+                rolls.remove(&r);
+            }
+        }
+        let expected = initial - rolls.len();
+
+        let area = Rect { origin: (0, 0), width: w as usize, height: h as usize };
+        let mut grid = RollGrid::from_rolls(example_rolls()).topology(Topology::Torus(area)).unwrap();
+        assert_eq!(grid.clone().remove_in_waves().total_removed(), expected);
+        assert_eq!(grid.remove_accesibles(), expected);
+
+        // on a 1-wide torus both vertical neighbors are the same cell
+        let grid = RollGrid::from_rolls([Roll::new(0, 0), Roll::new(0, 1)])
+            .topology(Topology::Torus(Rect { origin: (0, 0), width: 1, height: 2 }))
+            .unwrap();
+        // 3 offsets above, 3 below land on the other roll, the 2 sideways ones on the
+        // roll itself do not count
+        assert_eq!(grid.neighbor_counts().get(&Roll::new(0, 0)), Some(6));
+        let lone = RollGrid::from_rolls([Roll::new(0, 0)])
+            .topology(Topology::Torus(Rect { origin: (0, 0), width: 1, height: 1 }))
+            .unwrap();
+        assert_eq!(lone.neighbor_counts().get(&Roll::new(0, 0)), Some(0));
+        assert_eq!(lone.accessible_rolls(), 1);
+    }
+
+    #[test]
+    fn test_components() {
+        let rolls = [
+            Roll::new(0, 0),
+            Roll::new(1, 0),
+            Roll::new(0, 1),
+            Roll::new(1, 2), // diagonal to (0, 1)
+            Roll::new(4, 0),
+            Roll::new(4, 1),
+            Roll::new(2, 4),
+            Roll::new(3, 4),
+        ];
+        let grid = RollGrid::from_rolls(rolls);
+        let components = grid.components();
+        let sizes: Vec<usize> = components.iter().map(|c| c.size()).collect();
+        assert_eq!(sizes, vec![4, 2, 2]);
+        assert_eq!(components[0].bounds, Rect { origin: (0, 0), width: 2, height: 3 });
+        assert_eq!(components[1].rolls, vec![Roll::new(4, 0), Roll::new(4, 1)]);
+        assert_eq!(components[2].bounds, Rect { origin: (2, 4), width: 2, height: 1 });
+
+        // (0, 0) and (4, 0) touch across the wrapped edge
+        let area = Rect { origin: (0, 0), width: 5, height: 5 };
+        let wrapped = grid.topology(Topology::Torus(area)).unwrap();
+        assert_eq!(wrapped.components().len(), 1);
+    }
+
+    #[test]
+    fn test_component_survival() {
+        let grid = RollGrid::from_rolls(example_rolls());
+        let survival = grid.component_survival();
+        assert_eq!(survival.iter().map(|s| s.component.size()).sum::<usize>(), grid.len());
+        assert_eq!(survival.iter().map(|s| s.surviving).sum::<usize>(), grid.len() - 43);
+        assert!(survival.iter().any(|s| s.survives()));
+        // the grid itself is left alone
+        assert_eq!(grid.accessible_rolls(), 13);
+
+        let pair = RollGrid::from_rolls([Roll::new(0, 0), Roll::new(1, 0)]);
+        assert!(!pair.component_survival()[0].survives());
+    }
+
+    #[test]
+    fn test_incremental() {
+        let mut next = crate::test_rng::xorshift(0x9E3779B9);
+        let area = Rect { origin: (0, 0), width: 10, height: 10 };
+        let grids = [
+            RollGrid::from_rolls(example_rolls()),
+            sparse(&example_rolls()).neighborhood(Neighborhood::Offsets(vec![(1, 0), (2, 1), (0, -1)])),
+            RollGrid::from_rolls(example_rolls()).topology(Topology::Torus(area)).unwrap(),
+            RollGrid::from_rolls(example_rolls())
+                .topology(Topology::Bounded { area, walls: Walls::Occupied })
+                .unwrap(),
+        ];
+        for mut grid in grids {
+            for _ in 0..300 {
+                // a bit outside the example, to grow out of the dense area
+                let pos = Roll::new((next() % 13) as i32 - 1, (next() % 13) as i32 - 1);
+                if next().is_multiple_of(2) {
+                    let allowed = grid.topology.allows(&pos);
+                    assert_eq!(grid.insert(pos).is_ok(), allowed);
+                } else {
+                    grid.remove(&pos);
+                }
+                let fresh = grid.neighbor_counts();
+                for (r, c) in fresh.iter() {
+                    assert_eq!(grid.neighbor_count(&r), Some(c), "roll {:?}", r);
+                }
+                assert_eq!(grid.accessible_count(), grid.accessible().len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_what_if_matches_insert() {
+        let area = Rect { origin: (0, 0), width: 10, height: 10 };
+        let small = Rect { origin: (0, 0), width: 3, height: 2 };
+        let grids = [
+            RollGrid::from_rolls(example_rolls()),
+            sparse(&example_rolls()).neighborhood(Neighborhood::Offsets(vec![(1, 0), (2, 1), (0, -1)])),
+            RollGrid::from_rolls(example_rolls()).topology(Topology::Torus(area)).unwrap(),
+            RollGrid::from_rolls([Roll::new(0, 0)]).topology(Topology::Torus(small)).unwrap(),
+            // one column wide, so sideways offsets wrap back onto the roll
+            RollGrid::from_rolls([Roll::new(0, 1)])
+                .topology(Topology::Torus(Rect { origin: (0, 0), width: 1, height: 3 }))
+                .unwrap(),
+            RollGrid::from_rolls(example_rolls())
+                .topology(Topology::Bounded { area, walls: Walls::Occupied })
+                .unwrap(),
+        ];
+        for grid in grids {
+            // read-only, through a shared reference
+            let view = &grid;
+            for y in -1..11 {
+                for x in -1..11 {
+                    let pos = Roll::new(x, y);
+                    let expected = {
+                        let mut g = grid.clone();
+                        g.insert(pos).map(|_| g.accessible_count()).ok()
+                    };
+                    assert_eq!(view.accessible_if_inserted(pos).ok(), expected, "{:?} {:?}", pos, grid.topology);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_what_if() {
+        let mut grid = RollGrid::from_rolls(example_rolls());
+        assert_eq!(grid.accessible_count(), 13);
+        // filling a gap raises the counts of the rolls around it
+        assert!(grid.is_accessible(&Roll::new(2, 0)));
+        let count = grid.accessible_if_inserted(Roll::new(3, 1)).unwrap();
+        assert!(count < 13);
+        assert_eq!(grid.accessible_count(), 13);
+        assert_eq!(grid.neighbor_count(&Roll::new(3, 1)), None);
+
+        // asking about a cell outside the dense area keeps the dense store
+        assert_eq!(grid.accessible_if_inserted(Roll::new(-5, -5)).unwrap(), 14);
+        assert!(grid.store().is_dense());
+
+        assert!(grid.insert(Roll::new(-5, -5)).unwrap());
+        assert!(!grid.store().is_dense());
+        assert_eq!(grid.accessible_count(), 14);
+        assert!(grid.remove(&Roll::new(-5, -5)));
+        assert!(!grid.remove(&Roll::new(-5, -5)));
+        assert_eq!(grid.remove_accesibles(), 43);
+        assert_eq!(grid.accessible_count(), 0);
+    }
+
+    /// Smallest number of cells of `area` to fill so nothing is accessible, by trying all subsets.
+    fn brute_force_reinforcement(grid: &RollGrid, area: Rect) -> Option<usize> {
+        let empty: Vec<Roll> = area.cells().filter(|c| !grid.store().contains(c)).collect();
+        (0u32..(1 << empty.len()))
+            .filter(|mask| {
+                let mut g = grid.clone();
+                for (i, c) in empty.iter().enumerate() {
+                    if mask & (1 << i) != 0 {
+                        g.insert(*c).unwrap();
+                    }
+                }
+                g.accessible_rolls() == 0
+            })
+            .map(|mask| mask.count_ones() as usize)
+            .min()
+    }
+
+    #[test]
+    fn test_reinforce_exact() {
+        // on a 3x3 torus every cell neighbors every other
+        let area = Rect { origin: (0, 0), width: 3, height: 3 };
+        let grid = RollGrid::from_rolls([Roll::new(1, 1)]).topology(Topology::Torus(area)).unwrap();
+        let result = grid.reinforce().unwrap();
+        assert_eq!(result.added.len(), 4);
+        assert!(result.exact);
+
+        // a lone roll on an open plane always has an exposed corner
+        assert!(RollGrid::from_rolls([Roll::new(0, 0)]).reinforce().is_err());
+        assert!(RollGrid::from_rolls([]).reinforce().unwrap().added.is_empty());
+        let greater = grid.accessibility(Accessibility::new(Comparison::Greater, 2));
+        assert!(greater.reinforce().is_err());
+
+        let area = Rect { origin: (0, 0), width: 4, height: 3 };
+        let layouts: [&[Roll]; 4] = [
+            &[Roll::new(1, 1)],
+            &[Roll::new(0, 0), Roll::new(3, 2)],
+            &[Roll::new(1, 0), Roll::new(2, 1), Roll::new(1, 2)],
+            &[],
+        ];
+        for rolls in layouts {
+            for walls in [Walls::Occupied, Walls::Empty] {
+                let grid = RollGrid::from_rolls(rolls.iter().copied())
+                    .topology(Topology::Bounded { area, walls })
+                    .unwrap();
+                let expected = brute_force_reinforcement(&grid, area);
+                let result = grid.reinforce();
+                assert_eq!(result.as_ref().ok().map(|r| r.added.len()), expected, "{:?} {:?}", rolls, walls);
+                if let Ok(result) = result {
+                    let mut g = grid.clone();
+                    for c in &result.added {
+                        g.insert(*c).unwrap();
+                    }
+                    assert_eq!(g.accessible_rolls(), 0);
+                }
+            }
+        }
+
+        // on an open plane the search reaches as far as the neighborhood does
+        let cases = [
+            (vec![(2, 0), (-2, 0)], 1, Rect { origin: (-2, 0), width: 5, height: 1 }),
+            (vec![(2, 0), (-2, 0)], 2, Rect { origin: (-2, 0), width: 5, height: 1 }),
+            (vec![(2, 0), (-2, 0), (0, 1), (0, -1)], 2, Rect { origin: (-2, -1), width: 5, height: 3 }),
+        ];
+        for (offsets, threshold, area) in cases {
+            let grid = RollGrid::from_rolls([Roll::new(0, 0)])
+                .neighborhood(Neighborhood::Offsets(offsets.clone()))
+                .accessibility(Accessibility::new(Comparison::Less, threshold));
+            let expected = brute_force_reinforcement(&grid, area);
+            let result = grid.reinforce();
+            assert_eq!(result.map(|r| r.added.len()).ok(), expected, "{:?} < {}", offsets, threshold);
+        }
+        let far = [(2, 0), (-2, 0), (0, 2), (0, -2)];
+        let grid = RollGrid::from_rolls([Roll::new(0, 0)])
+            .neighborhood(Neighborhood::Offsets(far.to_vec()))
+            .accessibility(Accessibility::new(Comparison::Less, 1));
+        assert_eq!(grid.reinforce().unwrap().added.len(), 1);
+        let grid = RollGrid::from_rolls([Roll::new(0, 0), Roll::new(1, 0)]).neighborhood(Neighborhood::Radius(2));
+        let result = grid.reinforce().unwrap();
+        let mut g = grid.clone();
+        for c in &result.added {
+            g.insert(*c).unwrap();
+        }
+        assert_eq!(g.accessible_rolls(), 0);
+    }
+
+    #[test]
+    fn test_reinforce_out_of_budget() {
+        // 29 empty cells, few enough for the exact search to start
+        let area = Rect { origin: (0, 0), width: 10, height: 10 };
+        let grid = RollGrid::from_rolls(example_rolls())
+            .topology(Topology::Bounded { area, walls: Walls::Occupied })
+            .unwrap();
+        for budget in [1, 2, 3, 10, 100] {
+            let result = grid.reinforce_with_budget(budget).unwrap();
+            let mut g = grid.clone();
+            for c in &result.added {
+                assert!(g.insert(*c).unwrap(), "budget {}", budget);
+            }
+            assert_eq!(g.accessible_rolls(), 0, "budget {} exact {}", budget, result.exact);
+        }
+
+        let unreachable = grid.accessibility(Accessibility::new(Comparison::LessEq, u16::MAX));
+        assert!(unreachable.reinforce().is_err());
+    }
+
+    #[test]
+    fn test_reinforce_greedy() {
+        // the example tiled 2x2 has too many empty cells for the exact search
+        let tiled = example_rolls()
+            .into_iter()
+            .flat_map(|r| [(0, 0), (10, 0), (0, 10), (10, 10)].map(|(dx, dy)| Roll::new(r.pos.0 + dx, r.pos.1 + dy)));
+        let area = Rect { origin: (0, 0), width: 20, height: 20 };
+        let grid = RollGrid::from_rolls(tiled)
+            .topology(Topology::Bounded { area, walls: Walls::Occupied })
+            .unwrap();
+        let result = grid.reinforce().unwrap();
+        assert!(!result.exact);
+        assert!(result.added.len() <= 400 - grid.len());
+        let mut g = grid.clone();
+        for c in &result.added {
+            assert!(g.insert(*c).unwrap());
+        }
+        assert_eq!(g.accessible_rolls(), 0);
+    }
+
+    #[test]
+    fn test_parser() {
+        let (grid, area) = GridParser::default().parse(EXAMPLE).unwrap();
+        assert_eq!(area, Rect { origin: (0, 0), width: 10, height: 10 });
+        assert_eq!(grid.accessible_rolls(), 13);
+
+        let crlf = EXAMPLE.replace('\n', "\r\n") + "\r\n\r\n";
+        let (grid, area) = GridParser::default().parse(&crlf).unwrap();
+        assert_eq!(area.height, 10);
+        assert_eq!(grid.len(), example_rolls().len());
+
+        let err = GridParser::default().parse("@@.\n@x.").unwrap_err();
+        assert_eq!(err.to_string(), "unexpected character 'x' at line 2, column 2");
+        let err = GridParser::default().parse("@@.\n@\n...").unwrap_err();
+        assert_eq!(err.to_string(), "line 2 has 1 cells, expected 3");
+
+        let (grid, area) = GridParser::default().allow_ragged(true).parse("@\n\n.@@.").unwrap();
+        assert_eq!(area, Rect { origin: (0, 0), width: 4, height: 3 });
+        assert_eq!(grid.len(), 3);
+
+        let parser = GridParser::default().roll_glyphs("#O").empty_glyphs(". ");
+        let (grid, _) = parser.parse("#O.\n  #").unwrap();
+        assert_eq!(grid.render(), "xx.\n..x");
+        assert!(GridParser::default().roll_glyphs("@.").parse("@").is_err());
     }
-    Ok(RollGrid { rolls })
 }
@@ -1,99 +1,697 @@
+use std::cmp::{Ordering, Reverse};
+use std::fmt::{Debug, Display};
+use std::iter::Sum;
+use std::ops::{Add, Sub};
 use std::str::FromStr;
 
-use anyhow::{Context, anyhow};
+use ahash::AHashMap;
+use anyhow::{Context, anyhow, bail};
 
 pub fn solve() -> anyhow::Result<()> {
     let (input_ivs, input_points) = read_input()?;
-    let root = construct_interval_node(input_ivs.clone()).ok_or(anyhow!("Cannot construct interval tree"))?;
-
-    let mut total_fresh_ingredients = 0;
-    for point in input_points {
-        if overlap_any_point(Some(&root), point) {
-            total_fresh_ingredients += 1;
-        }
+    let fresh = IntervalSet::from_intervals(input_ivs);
+    if fresh.is_empty() {
+        return Err(anyhow!("No fresh ingredient ranges in input"));
     }
-    println!("Total fresh ingredients (PART 1): {}", total_fresh_ingredients);
 
-    let union = union(input_ivs);
-    let mut total_range = 0;
-    for iv in union {
-        total_range += iv.size();
+    let membership = fresh.contains_all(&input_points);
+    println!("Total fresh ingredients (PART 1): {}", membership.count);
+
+    println!("Total range (PART 2): {}", fresh.total_size());
+    Ok(())
+}
+
+/// Prints, instead of the answers, which ranges made each ingredient fresh, which
+/// ingredients matched nothing and which ranges matched no ingredient.
+pub fn explain(json: bool) -> anyhow::Result<()> {
+    let content = std::fs::read_to_string("inputs/input5.txt")?;
+    let (ranges, ids) = InventoryParser::default().parse_lines(&content)?;
+    let explanation = Explanation::new(ranges, &ids);
+    if json {
+        println!("{}", explanation.to_json());
+    } else {
+        print!("{}", explanation.to_table());
     }
-    println!("Total range (PART 2): {}", total_range);
     Ok(())
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Interval {
-    start: u64,
-    end: u64,
+/// Inclusive range `start..=end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Interval<T = u64> {
+    pub start: T,
+    pub end: T,
 }
 
-impl Interval {
+impl<T: Ord + Copy> Interval<T> {
+    pub fn new(start: T, end: T) -> Self {
+        Self { start, end }
+    }
+
     #[inline]
-    fn intersects(&self, point: u64) -> bool {
+    pub fn intersects(&self, point: T) -> bool {
         self.start <= point && point <= self.end
     }
 
+    /// Whether the two intervals share a point.
+    #[inline]
+    pub fn overlaps(&self, other: &Interval<T>) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+}
+
+impl<T: Discrete> Interval<T> {
+    /// Number of points, `3-7` has 5. Overflows if the interval spans the whole type.
     #[inline]
-    fn size(&self) -> u64 {
-        self.end - self.start + 1
+    pub fn size(&self) -> T::Size {
+        T::distance(self.start, self.end) + T::Size::from(1)
+    }
+}
+
+impl Interval<TotalF64> {
+    /// Length of the covered stretch of the real line, `end - start`.
+    pub fn length(&self) -> f64 {
+        self.end.0 - self.start.0
     }
 }
 
-impl FromStr for Interval {
+/// Endpoint types whose points can be counted: every value has a next and a previous
+/// one, so open bounds, adjacency and sizes are well defined.
+pub trait Discrete: Ord + Copy {
+    /// Counts points, wide enough for any `end - start` (unsigned for signed endpoints).
+    type Size: Copy + Ord + Debug + Default + From<u8> + Add<Output = Self::Size> + Sub<Output = Self::Size> + Sum;
+
+    /// The next value, `None` at the maximum.
+    fn succ(self) -> Option<Self>;
+    /// The previous value, `None` at the minimum.
+    fn pred(self) -> Option<Self>;
+    /// Steps from `start` up to `end`, for `start <= end`.
+    fn distance(start: Self, end: Self) -> Self::Size;
+}
+
+macro_rules! impl_discrete {
+    ($($t:ty => $size:ty),*) => {
+        $(
+            impl Discrete for $t {
+                type Size = $size;
+                #[inline]
+                fn succ(self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+                #[inline]
+                fn pred(self) -> Option<Self> {
+                    self.checked_sub(1)
+                }
+                #[inline]
+                fn distance(start: Self, end: Self) -> $size {
+                    end.abs_diff(start)
+                }
+            }
+        )*
+    };
+}
+
+impl_discrete!(
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128, usize => usize,
+    i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128, isize => usize
+);
+
+/// Calendar day, stored as days since 1970-01-01 (proleptic Gregorian). Parses and prints
+/// as `YYYY-MM-DD`, so expiry ranges read `2025-01-01-2025-03-31`. Timestamps need no
+/// type of their own, Unix seconds are plain `i64`s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date(pub i64);
+
+impl Date {
+    pub fn from_ymd(year: i64, month: u32, day: u32) -> anyhow::Result<Self> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            bail!("invalid date {:04}-{:02}-{:02}", year, month, day);
+        }
+        // days from civil, shifted so the year starts in March and leap days come last
+        let y = if month <= 2 { year - 1 } else { year };
+        let era = y.div_euclid(400);
+        let yoe = y - era * 400;
+        let mp = (month as i64 + 9) % 12;
+        let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        Ok(Self(era * 146097 + doe - 719468))
+    }
+
+    /// `(year, month, day)`
+    pub fn to_ymd(self) -> (i64, u32, u32) {
+        let z = self.0 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+        (year, month, day)
+    }
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Discrete for Date {
+    type Size = u64;
+    fn succ(self) -> Option<Self> {
+        self.0.succ().map(Date)
+    }
+    fn pred(self) -> Option<Self> {
+        self.0.pred().map(Date)
+    }
+    fn distance(start: Self, end: Self) -> u64 {
+        i64::distance(start.0, end.0)
+    }
+}
+
+impl FromStr for Date {
     type Err = anyhow::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts: Vec<_> = s.split('-').collect();
-        let start: u64 = parts
-            .get(0)
-            .ok_or(anyhow::anyhow!("invalid interval format: {}", s))?
-            .parse()
-            .context("interval")?;
-        let end: u64 = parts
-            .get(1)
-            .ok_or(anyhow::anyhow!("invalid interval format: {}", s))?
-            .parse()
-            .context("interval")?;
+        let mut parts = s.trim().splitn(3, '-');
+        let mut next = || parts.next().ok_or(anyhow!("invalid date {:?}, expected YYYY-MM-DD", s));
+        let year = next()?.parse().with_context(|| format!("year of {:?}", s))?;
+        let month = next()?.parse().with_context(|| format!("month of {:?}", s))?;
+        let day = next()?.parse().with_context(|| format!("day of {:?}", s))?;
+        Date::from_ymd(year, month, day)
+    }
+}
 
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (year, month, day) = self.to_ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+/// `f64` ordered by [`f64::total_cmp`], so it can be an interval endpoint. `-0.0` sorts
+/// before `0.0` and NaNs sort at the ends.
+#[derive(Debug, Clone, Copy)]
+pub struct TotalF64(pub f64);
+
+impl PartialEq for TotalF64 {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TotalF64 {}
+
+impl PartialOrd for TotalF64 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TotalF64 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl FromStr for TotalF64 {
+    type Err = std::num::ParseFloatError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(TotalF64)
+    }
+}
+
+impl Display for TotalF64 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl<T: Ord + Copy + FromStr + Display> FromStr for Interval<T>
+where
+    T::Err: Display,
+{
+    type Err = anyhow::Error;
+    /// `start-end` with `start <= end`, surrounding whitespace allowed. Endpoints may
+    /// contain dashes themselves, like `-5--3` or `2025-01-01-2025-03-31`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = parse_dashed(s)?;
+        if start > end {
+            bail!("reversed interval {:?}: start {} is after end {}", s.trim(), start, end);
+        }
         Ok(Self { start, end })
     }
 }
 
+/// Endpoints of `start-end`, in whatever order they are written.
+fn parse_dashed<T: FromStr>(s: &str) -> anyhow::Result<(T, T)>
+where
+    T::Err: Display,
+{
+    let s = s.trim();
+    let dashes: Vec<usize> = s.match_indices('-').map(|(at, _)| at).collect();
+    let parse = |part: &str, what: &str| part.trim().parse::<T>().map_err(|e| anyhow!("interval {} {:?}: {}", what, part.trim(), e));
+    match dashes[..] {
+        [] => bail!("invalid interval format: {}", s),
+        // the usual case, keeping the endpoint's own error
+        [at] => Ok((parse(&s[..at], "start")?, parse(&s[at + 1..], "end")?)),
+        _ => {
+            // signed numbers and dates bring their own dashes, only one split can work
+            let mut splits = dashes
+                .iter()
+                .filter_map(|&at| Some((parse(&s[..at], "start").ok()?, parse(&s[at + 1..], "end").ok()?)));
+            match (splits.next(), splits.next()) {
+                (Some(split), None) => Ok(split),
+                (None, _) => bail!("invalid interval format: {}, expected exactly two endpoints", s),
+                (Some(_), Some(_)) => bail!("ambiguous interval: {}", s),
+            }
+        }
+    }
+}
+
+/// Whether an interval endpoint belongs to the interval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum BoundKind {
+    #[default]
+    Closed,
+    Open,
+}
+
+/// Interval with its own bound kind on each end, like `[3,7)` or `(3,7]`. The points are
+/// discrete, so every non-empty one is equivalent to some inclusive [`Interval`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BoundedInterval<T = u64> {
+    pub start: T,
+    pub start_kind: BoundKind,
+    pub end: T,
+    pub end_kind: BoundKind,
+}
+
+impl<T: Discrete> BoundedInterval<T> {
+    pub fn new(start: T, start_kind: BoundKind, end: T, end_kind: BoundKind) -> Self {
+        Self { start, start_kind, end, end_kind }
+    }
+
+    /// `[start, end]`
+    pub fn closed(start: T, end: T) -> Self {
+        Self::new(start, BoundKind::Closed, end, BoundKind::Closed)
+    }
+
+    /// `[start, end)`
+    pub fn half_open(start: T, end: T) -> Self {
+        Self::new(start, BoundKind::Closed, end, BoundKind::Open)
+    }
+
+    /// `(start, end)`
+    pub fn open(start: T, end: T) -> Self {
+        Self::new(start, BoundKind::Open, end, BoundKind::Open)
+    }
+
+    /// The same points as an inclusive interval, `None` if there are none.
+    pub fn to_closed(&self) -> Option<Interval<T>> {
+        let start = match self.start_kind {
+            BoundKind::Closed => self.start,
+            BoundKind::Open => self.start.succ()?,
+        };
+        let end = match self.end_kind {
+            BoundKind::Closed => self.end,
+            BoundKind::Open => self.end.pred()?,
+        };
+        (start <= end).then_some(Interval::new(start, end))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.to_closed().is_none()
+    }
+
+    /// Number of points, `[3,7)` has 4.
+    pub fn size(&self) -> T::Size {
+        self.to_closed().map_or_else(T::Size::default, |iv| iv.size())
+    }
+
+    pub fn contains(&self, point: T) -> bool {
+        self.to_closed().is_some_and(|iv| iv.intersects(point))
+    }
+
+    /// Points in both, keeping the tighter bound kind where the endpoints tie. May be empty.
+    pub fn intersection(&self, other: &BoundedInterval<T>) -> BoundedInterval<T> {
+        let (start, start_kind) = start_key(self).max(start_key(other));
+        let (end, Reverse(end_kind)) = end_key(self).min(end_key(other));
+        Self::new(start, start_kind, end, end_kind)
+    }
+
+    /// The union as a single interval, if the two overlap or touch.
+    pub fn merge(&self, other: &BoundedInterval<T>) -> Option<BoundedInterval<T>> {
+        let (a, b) = match (self.to_closed(), other.to_closed()) {
+            (None, _) => return Some(*other),
+            (_, None) => return Some(*self),
+            (Some(a), Some(b)) => (a, b),
+        };
+        let touches = |x: &Interval<T>, y: &Interval<T>| x.end.succ().is_none_or(|next| y.start <= next);
+        if !(touches(&a, &b) && touches(&b, &a)) {
+            return None;
+        }
+        let (start, start_kind) = start_key(self).min(start_key(other));
+        let (end, Reverse(end_kind)) = end_key(self).max(end_key(other));
+        Some(Self::new(start, start_kind, end, end_kind))
+    }
+}
+
+/// Orders starts by where they begin: `[3` before `(3`.
+fn start_key<T: Copy>(iv: &BoundedInterval<T>) -> (T, BoundKind) {
+    (iv.start, iv.start_kind)
+}
+
+/// Orders ends by where they stop: `3)` before `3]`.
+fn end_key<T: Copy>(iv: &BoundedInterval<T>) -> (T, Reverse<BoundKind>) {
+    (iv.end, Reverse(iv.end_kind))
+}
+
+impl<T: Discrete> From<Interval<T>> for BoundedInterval<T> {
+    fn from(iv: Interval<T>) -> Self {
+        Self::closed(iv.start, iv.end)
+    }
+}
+
+impl<T: Display> Display for BoundedInterval<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let open = if self.start_kind == BoundKind::Closed { '[' } else { '(' };
+        let close = if self.end_kind == BoundKind::Closed { ']' } else { ')' };
+        write!(f, "{}{},{}{}", open, self.start, self.end, close)
+    }
+}
+
+impl<T: Discrete + FromStr + Display> FromStr for BoundedInterval<T>
+where
+    T::Err: Display,
+{
+    type Err = anyhow::Error;
+    /// Either the puzzle's inclusive `3-7` or bracket notation `[3,7)`, `(3,7]`, `(3,7)`,
+    /// with `start <= end`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let iv = BoundedInterval::parse_unordered(s)?;
+        if iv.start > iv.end {
+            bail!("reversed interval {:?}: start {} is after end {}", s.trim(), iv.start, iv.end);
+        }
+        Ok(iv)
+    }
+}
+
+impl<T: Discrete + FromStr> BoundedInterval<T>
+where
+    T::Err: Display,
+{
+    fn parse_unordered(s: &str) -> anyhow::Result<Self> {
+        let s = s.trim();
+        let start_kind = match s.chars().next() {
+            Some('[') => BoundKind::Closed,
+            Some('(') => BoundKind::Open,
+            _ => return parse_dashed(s).map(|(start, end)| Self::closed(start, end)),
+        };
+        let end_kind = match s.chars().last() {
+            Some(']') => BoundKind::Closed,
+            Some(')') => BoundKind::Open,
+            _ => bail!("unclosed interval: {}", s),
+        };
+        let (start, end) = s[1..s.len() - 1]
+            .split_once(',')
+            .ok_or(anyhow!("invalid interval format: {}", s))?;
+        if end.contains(',') {
+            bail!("invalid interval format: {}, expected exactly two endpoints", s);
+        }
+        let start = start.trim().parse().map_err(|e| anyhow!("interval start {:?}: {}", start.trim(), e))?;
+        let end = end.trim().parse().map_err(|e| anyhow!("interval end {:?}: {}", end.trim(), e))?;
+        Ok(Self::new(start, start_kind, end, end_kind))
+    }
+
+    /// The same interval written the other way round, `(7,3]` becomes `[3,7)`.
+    fn reversed(self) -> Self {
+        Self::new(self.end, self.end_kind, self.start, self.start_kind)
+    }
+}
+
+/// A fresh range as written in the input, with its 1-based line number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputRange {
+    pub line: usize,
+    pub range: BoundedInterval,
+}
+
+impl std::fmt::Display for InputRange {
+    /// `line 3 10-14`, bracket notation unless both ends are closed.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let r = &self.range;
+        if r.start_kind == BoundKind::Closed && r.end_kind == BoundKind::Closed {
+            write!(f, "line {} {}-{}", self.line, r.start, r.end)
+        } else {
+            write!(f, "line {} {}", self.line, r)
+        }
+    }
+}
+
+/// Parses the puzzle input: fresh ranges, one blank line, then ingredient IDs.
+/// Ranges may use any [`BoundedInterval`] syntax. Reversed ranges like `9-3` are rejected
+/// unless normalization is enabled.
+#[derive(Debug, Clone, Default)]
+pub struct InventoryParser {
+    normalize_reversed: bool,
+}
+
+impl InventoryParser {
+    /// Reads reversed ranges as if their endpoints were swapped instead of failing.
+    pub fn normalize_reversed(mut self, normalize: bool) -> Self {
+        self.normalize_reversed = normalize;
+        self
+    }
+
+    /// Returns the ranges, inclusive and without empty ones like `[3,3)`, and the
+    /// ingredient IDs. Surrounding whitespace, `\r\n` line endings and trailing blank
+    /// lines are accepted.
+    pub fn parse(&self, content: &str) -> anyhow::Result<(Vec<Interval>, Vec<u64>)> {
+        let (ranges, ids) = self.parse_lines(content)?;
+        // empty ranges hold no ingredient
+        let intervals = ranges.iter().filter_map(|r| r.range.to_closed()).collect();
+        Ok((intervals, ids))
+    }
+
+    /// Like [`parse`](Self::parse), but keeps every range as written, empty ones
+    /// included, with its line number.
+    pub fn parse_lines(&self, content: &str) -> anyhow::Result<(Vec<InputRange>, Vec<u64>)> {
+        let lines: Vec<&str> = content.lines().map(str::trim).collect();
+        let Some(separator) = lines.iter().position(|l| l.is_empty()) else {
+            bail!("missing blank line between ranges and ingredient IDs");
+        };
+        let height = lines.iter().rposition(|l| !l.is_empty()).map_or(0, |i| i + 1).max(separator + 1);
+
+        let mut ranges = Vec::with_capacity(separator);
+        for (i, line) in lines[..separator].iter().enumerate() {
+            let range = self.parse_range(line).with_context(|| format!("line {}", i + 1))?;
+            ranges.push(InputRange { line: i + 1, range });
+        }
+        let mut ids = Vec::with_capacity(height - separator - 1);
+        for (i, line) in lines.iter().enumerate().take(height).skip(separator + 1) {
+            if line.is_empty() {
+                bail!("unexpected blank line at line {}, input must have exactly two sections", i + 1);
+            }
+            let id = line.parse().with_context(|| format!("invalid ingredient ID {:?} at line {}", line, i + 1))?;
+            ids.push(id);
+        }
+        Ok((ranges, ids))
+    }
+
+    fn parse_range(&self, line: &str) -> anyhow::Result<BoundedInterval> {
+        if !self.normalize_reversed {
+            return line.parse();
+        }
+        let iv = BoundedInterval::parse_unordered(line)?;
+        Ok(if iv.start > iv.end { iv.reversed() } else { iv })
+    }
+}
+
 fn read_input() -> anyhow::Result<(Vec<Interval>, Vec<u64>)> {
     let content = std::fs::read_to_string("inputs/input5.txt")?;
-    let mut intervals = Vec::new();
-    let mut ids = Vec::new();
-    let mut intervals_end = false;
-    for line in content.lines() {
-        if line.is_empty() {
-            intervals_end = true;
-            continue;
-        }
-        if intervals_end {
-            ids.push(line.parse()?);
-        } else {
-            intervals.push(line.parse()?);
+    InventoryParser::default().parse(&content)
+}
+
+/// Static centered interval tree: each node keeps the intervals containing its center,
+/// sorted by start and by end, smaller ones go left and larger ones right.
+#[derive(Debug, Clone)]
+pub struct IntervalTree<T: Ord + Copy = u64> {
+    root: Option<Box<IntervalTreeNode<T>>>,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+struct IntervalTreeNode<T> {
+    center: T,
+    left_set: Option<Box<IntervalTreeNode<T>>>,
+    right_set: Option<Box<IntervalTreeNode<T>>>,
+    intervals_start: Vec<Interval<T>>,
+    intervals_end: Vec<Interval<T>>,
+}
+
+impl<T: Ord + Copy> IntervalTree<T> {
+    pub fn from_intervals(intervals: impl IntoIterator<Item = Interval<T>>) -> Self {
+        let intervals: Vec<_> = intervals.into_iter().collect();
+        let len = intervals.len();
+        Self { root: construct_interval_node(intervals), len }
+    }
+
+    /// Number of intervals in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether any interval contains `point`.
+    pub fn contains_point(&self, point: T) -> bool {
+        overlap_any_point(self.root.as_deref(), point)
+    }
+
+    /// How many intervals contain `point`, without collecting them.
+    pub fn stab_count(&self, point: T) -> usize {
+        let mut count = 0;
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            if point < n.center {
+                count += n.intervals_start.partition_point(|iv| iv.start <= point);
+                node = n.left_set.as_deref();
+            } else if point > n.center {
+                count += n.intervals_end.len() - n.intervals_end.partition_point(|iv| iv.end < point);
+                node = n.right_set.as_deref();
+            } else {
+                count += n.intervals_start.len();
+                break;
+            }
+        }
+        count
+    }
+
+    /// All intervals containing `point`, in no particular order.
+    pub fn query_point(&self, point: T) -> Vec<Interval<T>> {
+        let mut found = Vec::new();
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            if point < n.center {
+                // these all reach the center, so starting early enough is enough
+                found.extend(n.intervals_start.iter().take_while(|iv| iv.start <= point));
+                node = n.left_set.as_deref();
+            } else if point > n.center {
+                found.extend(n.intervals_end.iter().rev().take_while(|iv| iv.end >= point));
+                node = n.right_set.as_deref();
+            } else {
+                found.extend(&n.intervals_start);
+                break;
+            }
+        }
+        found
+    }
+
+    /// All intervals sharing a point with `range`, in no particular order.
+    pub fn query_range(&self, range: Interval<T>) -> Vec<Interval<T>> {
+        let mut found = Vec::new();
+        let mut stack: Vec<&IntervalTreeNode<T>> = self.root.as_deref().into_iter().collect();
+        while let Some(n) = stack.pop() {
+            if range.end < n.center {
+                found.extend(n.intervals_start.iter().take_while(|iv| iv.start <= range.end));
+                stack.extend(n.left_set.as_deref());
+            } else if range.start > n.center {
+                found.extend(n.intervals_end.iter().rev().take_while(|iv| iv.end >= range.start));
+                stack.extend(n.right_set.as_deref());
+            } else {
+                // the range holds the center
+                found.extend(&n.intervals_start);
+                stack.extend(n.left_set.as_deref());
+                stack.extend(n.right_set.as_deref());
+            }
+        }
+        found
+    }
+}
+
+impl<T: Ord + Copy> IntervalTree<T> {
+    /// Shape of the tree: depth, node count and how the intervals spread over the nodes.
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats::default();
+        let mut stack: Vec<(&IntervalTreeNode<T>, usize)> = self.root.as_deref().map(|n| (n, 1)).into_iter().collect();
+        while let Some((n, depth)) = stack.pop() {
+            stats.depth = stats.depth.max(depth);
+            stats.intervals_per_node.push(n.intervals_start.len());
+            // right first so nodes come out in preorder
+            stack.extend(n.right_set.as_deref().map(|r| (r, depth + 1)));
+            stack.extend(n.left_set.as_deref().map(|l| (l, depth + 1)));
+        }
+        stats
+    }
+}
+
+impl<T: Ord + Copy + std::fmt::Display> IntervalTree<T> {
+    /// Graphviz DOT of the tree, each node showing its center and intervals.
+    pub fn to_dot(&self) -> String {
+        use std::fmt::Write;
+        let mut dot = String::from("digraph interval_tree {\n    node [shape=box];\n");
+        let mut next_id = 0;
+        let mut stack: Vec<(&IntervalTreeNode<T>, usize)> = Vec::new();
+        if let Some(root) = self.root.as_deref() {
+            stack.push((root, next_id));
+            next_id += 1;
         }
+        while let Some((n, id)) = stack.pop() {
+            let intervals: Vec<String> = n.intervals_start.iter().map(|iv| format!("{}-{}", iv.start, iv.end)).collect();
+            // writing to a String cannot fail
+            writeln!(dot, "    n{} [label=\"center {}\\n{}\"];", id, n.center, intervals.join("\\n")).unwrap();
+            for (child, side) in [(n.left_set.as_deref(), "L"), (n.right_set.as_deref(), "R")] {
+                if let Some(child) = child {
+                    writeln!(dot, "    n{} -> n{} [label={}];", id, next_id, side).unwrap();
+                    stack.push((child, next_id));
+                    next_id += 1;
+                }
+            }
+        }
+        dot.push_str("}\n");
+        dot
     }
-    Ok((intervals, ids))
 }
 
-struct IntervalTreeNode {
-    center: u64,
-    left_set: Option<Box<IntervalTreeNode>>,
-    right_set: Option<Box<IntervalTreeNode>>,
-    intervals_start: Vec<Interval>,
-    intervals_end: Vec<Interval>,
+/// Result of [`IntervalTree::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeStats {
+    /// Levels of nodes, 0 for an empty tree.
+    pub depth: usize,
+    /// Intervals stored at each node, in preorder.
+    pub intervals_per_node: Vec<usize>,
 }
 
-fn find_pivot(intervals: &[Interval]) -> u64 {
+impl TreeStats {
+    pub fn node_count(&self) -> usize {
+        self.intervals_per_node.len()
+    }
+
+    /// Most intervals held by a single node.
+    pub fn max_node_intervals(&self) -> usize {
+        self.intervals_per_node.iter().copied().max().unwrap_or(0)
+    }
+}
+
+impl<T: Ord + Copy> FromIterator<Interval<T>> for IntervalTree<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        Self::from_intervals(iter)
+    }
+}
+
+fn find_pivot<T: Ord + Copy>(intervals: &[Interval<T>]) -> T {
     let mut points: Vec<_> = intervals.iter().flat_map(|i| [i.start, i.end]).collect();
     points.sort();
     points[points.len() / 2]
 }
 
-fn overlap_any_point(node: Option<&IntervalTreeNode>, point: u64) -> bool {
+fn overlap_any_point<T: Ord + Copy>(node: Option<&IntervalTreeNode<T>>, point: T) -> bool {
     let Some(node) = node else {
         return false;
     };
@@ -123,76 +721,707 @@ fn overlap_any_point(node: Option<&IntervalTreeNode>, point: u64) -> bool {
     }
 }
 
-fn construct_interval_node(intervals: Vec<Interval>) -> Option<Box<IntervalTreeNode>> {
-    // Stop.
-    if intervals.is_empty() {
-        return None;
+/// Pending work of [`construct_interval_node`].
+enum BuildStep<T> {
+    /// build a subtree from these intervals
+    Split(Vec<Interval<T>>),
+    /// join the two subtrees built last under a node with this center
+    Join { center: T, intervals: Vec<Interval<T>> },
+}
+
+/// Builds the tree with an explicit stack, so no input can exhaust the call stack. The
+/// median pivot leaves at most half of the intervals to each side, so the depth stays
+/// below log2(n) + 1 anyway.
+fn construct_interval_node<T: Ord + Copy>(intervals: Vec<Interval<T>>) -> Option<Box<IntervalTreeNode<T>>> {
+    let mut steps = vec![BuildStep::Split(intervals)];
+    let mut built: Vec<Option<Box<IntervalTreeNode<T>>>> = Vec::new();
+    while let Some(step) = steps.pop() {
+        match step {
+            // Stop.
+            BuildStep::Split(intervals) if intervals.is_empty() => built.push(None),
+            BuildStep::Split(intervals) => {
+                // find pivot
+                let pivot = find_pivot(&intervals);
+
+                // sort out
+                let mut left_ivs = Vec::new();
+                let mut right_ivs = Vec::new();
+                let mut center_ivs = Vec::new();
+
+                for iv in intervals {
+                    if iv.end < pivot {
+                        left_ivs.push(iv);
+                    } else if iv.start > pivot {
+                        right_ivs.push(iv);
+                    } else {
+                        center_ivs.push(iv);
+                    }
+                }
+
+                // left is built first, so it ends up below right on `built`
+                steps.push(BuildStep::Join { center: pivot, intervals: center_ivs });
+                steps.push(BuildStep::Split(right_ivs));
+                steps.push(BuildStep::Split(left_ivs));
+            }
+            BuildStep::Join { center, intervals } => {
+                // two list: sorted with start and sorted with end
+                let mut ivs_start = intervals.clone();
+                ivs_start.sort_by_key(|iv| iv.start);
+
+                let mut ivs_end = intervals;
+                ivs_end.sort_by_key(|iv| iv.end);
+
+                let right_set = built.pop().flatten();
+                let left_set = built.pop().flatten();
+                built.push(Some(Box::new(IntervalTreeNode {
+                    center,
+                    left_set,
+                    right_set,
+                    intervals_start: ivs_start,
+                    intervals_end: ivs_end,
+                })));
+            }
+        }
+    }
+    built.pop().flatten()
+}
+
+/// Interval tree supporting insertion and deletion: an AVL tree keyed by `(start, end)`
+/// where each node also knows the largest end in its subtree. Height stays within
+/// about 1.44·log2(n). Equal intervals are kept as a count on one node.
+#[derive(Debug, Clone)]
+pub struct DynamicIntervalTree<T: Ord + Copy = u64> {
+    root: AvlLink<T>,
+    len: usize,
+}
+
+type AvlLink<T> = Option<Box<AvlNode<T>>>;
+
+#[derive(Debug, Clone)]
+struct AvlNode<T> {
+    interval: Interval<T>,
+    /// copies of `interval`
+    count: usize,
+    /// largest end in this subtree
+    max_end: T,
+    height: u32,
+    left: AvlLink<T>,
+    right: AvlLink<T>,
+}
+
+impl<T: Ord + Copy> Default for DynamicIntervalTree<T> {
+    fn default() -> Self {
+        Self { root: None, len: 0 }
+    }
+}
+
+impl<T: Ord + Copy> DynamicIntervalTree<T> {
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    // find pivot
-    let pivot = find_pivot(&intervals);
+    /// Bulk load: sorts once and builds a perfectly balanced tree, faster than inserting
+    /// one by one.
+    pub fn from_intervals(intervals: impl IntoIterator<Item = Interval<T>>) -> Self {
+        let mut intervals: Vec<_> = intervals.into_iter().collect();
+        let len = intervals.len();
+        intervals.sort_by_key(|iv| (iv.start, iv.end));
+        let mut grouped: Vec<(Interval<T>, usize)> = Vec::with_capacity(len);
+        for iv in intervals {
+            match grouped.last_mut() {
+                Some((last, count)) if *last == iv => *count += 1,
+                _ => grouped.push((iv, 1)),
+            }
+        }
+        Self { root: avl_build(&grouped), len }
+    }
 
-    // sort out
-    let mut left_ivs = Vec::new();
-    let mut right_ivs = Vec::new();
-    let mut center_ivs = Vec::new();
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-    for iv in intervals {
-        if iv.end < pivot {
-            left_ivs.push(iv);
-        } else if iv.start > pivot {
-            right_ivs.push(iv);
-        } else {
-            center_ivs.push(iv);
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Height of the tree, 0 when empty.
+    pub fn height(&self) -> u32 {
+        avl_height(&self.root)
+    }
+
+    pub fn insert(&mut self, interval: Interval<T>) {
+        self.root = Some(avl_insert(self.root.take(), interval));
+        self.len += 1;
+    }
+
+    /// Removes one copy of `interval`, returns false if there was none.
+    pub fn remove(&mut self, interval: &Interval<T>) -> bool {
+        let mut removed = false;
+        self.root = avl_remove(self.root.take(), interval, &mut removed);
+        if removed {
+            self.len -= 1;
         }
+        removed
+    }
+
+    /// Whether any interval contains `point`.
+    pub fn contains_point(&self, point: T) -> bool {
+        let mut node = self.root.as_deref();
+        while let Some(n) = node {
+            if n.max_end < point {
+                return false;
+            }
+            if n.interval.intersects(point) {
+                return true;
+            }
+            // if the left subtree reaches the point but holds no match, everything in it
+            // starts after the point, and so does everything to the right
+            node = match n.left.as_deref() {
+                Some(left) if left.max_end >= point => Some(left),
+                _ if n.interval.start <= point => n.right.as_deref(),
+                _ => None,
+            };
+        }
+        false
+    }
+
+    /// How many intervals contain `point`, copies included.
+    pub fn stab_count(&self, point: T) -> usize {
+        let mut count = 0;
+        avl_query(&self.root, Interval::new(point, point), &mut |node| count += node.count);
+        count
+    }
+
+    /// All intervals containing `point`, in `(start, end)` order.
+    pub fn query_point(&self, point: T) -> Vec<Interval<T>> {
+        self.query_range(Interval::new(point, point))
+    }
+
+    /// All intervals sharing a point with `range`, in `(start, end)` order.
+    pub fn query_range(&self, range: Interval<T>) -> Vec<Interval<T>> {
+        let mut found = Vec::new();
+        avl_query(&self.root, range, &mut |node| {
+            found.extend(std::iter::repeat_n(node.interval, node.count));
+        });
+        found
+    }
+}
+
+impl<T: Ord + Copy> FromIterator<Interval<T>> for DynamicIntervalTree<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        Self::from_intervals(iter)
     }
+}
+
+fn avl_height<T>(link: &AvlLink<T>) -> u32 {
+    link.as_ref().map_or(0, |n| n.height)
+}
+
+fn avl_new<T: Copy>(interval: Interval<T>, count: usize) -> Box<AvlNode<T>> {
+    Box::new(AvlNode { interval, count, max_end: interval.end, height: 1, left: None, right: None })
+}
+
+/// Recomputes height and max end from the children.
+fn avl_update<T: Ord + Copy>(node: &mut AvlNode<T>) {
+    node.height = 1 + avl_height(&node.left).max(avl_height(&node.right));
+    node.max_end = [&node.left, &node.right]
+        .into_iter()
+        .flatten()
+        .map(|child| child.max_end)
+        .fold(node.interval.end, T::max);
+}
+
+fn avl_rotate_right<T: Ord + Copy>(mut node: Box<AvlNode<T>>) -> Box<AvlNode<T>> {
+    let mut left = node.left.take().expect("rotating right needs a left child");
+    node.left = left.right.take();
+    avl_update(&mut node);
+    left.right = Some(node);
+    avl_update(&mut left);
+    left
+}
+
+fn avl_rotate_left<T: Ord + Copy>(mut node: Box<AvlNode<T>>) -> Box<AvlNode<T>> {
+    let mut right = node.right.take().expect("rotating left needs a right child");
+    node.right = right.left.take();
+    avl_update(&mut node);
+    right.left = Some(node);
+    avl_update(&mut right);
+    right
+}
 
-    // two list: sorted with start and sorted with end
-    let mut ivs_start = center_ivs.clone();
-    ivs_start.sort_by_key(|iv| iv.start);
+/// Restores the AVL property at `node`, whose subtrees differ in height by at most 2.
+fn avl_balance<T: Ord + Copy>(mut node: Box<AvlNode<T>>) -> Box<AvlNode<T>> {
+    avl_update(&mut node);
+    let (lh, rh) = (avl_height(&node.left), avl_height(&node.right));
+    if lh > rh + 1 {
+        let left = node.left.take().unwrap();
+        node.left = Some(if avl_height(&left.left) < avl_height(&left.right) {
+            avl_rotate_left(left)
+        } else {
+            left
+        });
+        avl_rotate_right(node)
+    } else if rh > lh + 1 {
+        let right = node.right.take().unwrap();
+        node.right = Some(if avl_height(&right.right) < avl_height(&right.left) {
+            avl_rotate_right(right)
+        } else {
+            right
+        });
+        avl_rotate_left(node)
+    } else {
+        node
+    }
+}
 
-    let mut ivs_end = center_ivs.clone();
-    ivs_end.sort_by_key(|iv| iv.end);
+fn avl_build<T: Ord + Copy>(sorted: &[(Interval<T>, usize)]) -> AvlLink<T> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let mid = sorted.len() / 2;
+    let (interval, count) = sorted[mid];
+    let mut node = avl_new(interval, count);
+    node.left = avl_build(&sorted[..mid]);
+    node.right = avl_build(&sorted[(mid + 1)..]);
+    avl_update(&mut node);
+    Some(node)
+}
 
-    // construct left and right nodes
-    let left_set = construct_interval_node(left_ivs);
-    let right_set = construct_interval_node(right_ivs);
-    Some(Box::new(IntervalTreeNode {
-        center: pivot,
-        left_set,
-        right_set,
-        intervals_start: ivs_start,
-        intervals_end: ivs_end,
-    }))
+fn avl_insert<T: Ord + Copy>(link: AvlLink<T>, interval: Interval<T>) -> Box<AvlNode<T>> {
+    let Some(mut node) = link else {
+        return avl_new(interval, 1);
+    };
+    match (interval.start, interval.end).cmp(&(node.interval.start, node.interval.end)) {
+        Ordering::Less => node.left = Some(avl_insert(node.left.take(), interval)),
+        Ordering::Greater => node.right = Some(avl_insert(node.right.take(), interval)),
+        Ordering::Equal => node.count += 1,
+    }
+    avl_balance(node)
+}
+
+fn avl_remove<T: Ord + Copy>(link: AvlLink<T>, interval: &Interval<T>, removed: &mut bool) -> AvlLink<T> {
+    let mut node = link?;
+    match (interval.start, interval.end).cmp(&(node.interval.start, node.interval.end)) {
+        Ordering::Less => node.left = avl_remove(node.left.take(), interval, removed),
+        Ordering::Greater => node.right = avl_remove(node.right.take(), interval, removed),
+        Ordering::Equal => {
+            *removed = true;
+            if node.count > 1 {
+                node.count -= 1;
+            } else {
+                match (node.left.take(), node.right.take()) {
+                    (None, right) => return right,
+                    (left, None) => return left,
+                    (left, Some(right)) => {
+                        // replace with the successor
+                        let (right, mut successor) = avl_remove_min(right);
+                        successor.left = left;
+                        successor.right = right;
+                        return Some(avl_balance(successor));
+                    }
+                }
+            }
+        }
+    }
+    Some(avl_balance(node))
+}
+
+/// Detaches the leftmost node, returning the rest of the subtree and that node.
+fn avl_remove_min<T: Ord + Copy>(mut node: Box<AvlNode<T>>) -> (AvlLink<T>, Box<AvlNode<T>>) {
+    match node.left.take() {
+        None => (node.right.take(), node),
+        Some(left) => {
+            let (left, min) = avl_remove_min(left);
+            node.left = left;
+            (Some(avl_balance(node)), min)
+        }
+    }
+}
+
+/// Calls `visit` on every node whose interval overlaps `range`, in key order.
+fn avl_query<T: Ord + Copy>(link: &AvlLink<T>, range: Interval<T>, visit: &mut impl FnMut(&AvlNode<T>)) {
+    let Some(node) = link else {
+        return;
+    };
+    // nothing in here reaches the range
+    if node.max_end < range.start {
+        return;
+    }
+    avl_query(&node.left, range, visit);
+    if node.interval.overlaps(&range) {
+        visit(node);
+    }
+    // everything to the right starts at or after this node
+    if node.interval.start <= range.end {
+        avl_query(&node.right, range, visit);
+    }
 }
 
 // ============ PART 2 ============ //
-fn union(mut intervals: Vec<Interval>) -> Vec<Interval> {
-    if intervals.is_empty() {
-        return vec![];
-    }
-    // First sort the intervals
-    intervals.sort_by_key(|iv| iv.start);
-    // contains discrete intervals
-    let mut set = Vec::new();
-    // bounds of largest continous interval range
-    let mut low = intervals[0].start;
-    let mut high = intervals[0].end;
-
-    for iv in &intervals[1..] {
-        // discrete interval found
-        if iv.start > high {
-            // push current
-            set.push(Interval {start: low, end : high});
-            low = iv.start;
-            high = iv.end;
-        } else if iv.end > high {
-            high = iv.end;
-        }
-    }
-    // push last
-    set.push(Interval { start: low, end: high });
-    set
+/// Set of points stored as sorted, disjoint, non-adjacent intervals, so two sets with the
+/// same points always have the same representation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IntervalSet<T = u64> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T> Default for IntervalSet<T> {
+    fn default() -> Self {
+        Self { intervals: Vec::new() }
+    }
+}
+
+impl<T: Discrete> IntervalSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Normalizes arbitrary (possibly overlapping) intervals.
+    pub fn from_intervals(intervals: impl IntoIterator<Item = Interval<T>>) -> Self {
+        let mut intervals: Vec<_> = intervals.into_iter().collect();
+        intervals.sort_by_key(|iv| iv.start);
+        let mut merged: Vec<Interval<T>> = Vec::with_capacity(intervals.len());
+        for iv in intervals {
+            match merged.last_mut() {
+                // overlapping or touching
+                Some(last) if last.end.succ().is_none_or(|next| iv.start <= next) => {
+                    last.end = last.end.max(iv.end);
+                }
+                _ => merged.push(iv),
+            }
+        }
+        Self { intervals: merged }
+    }
+
+    /// The disjoint intervals, sorted.
+    pub fn intervals(&self) -> &[Interval<T>] {
+        &self.intervals
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Interval<T>> + '_ {
+        self.intervals.iter().copied()
+    }
+
+    /// Like [`from_intervals`](Self::from_intervals) for intervals with any bound kinds.
+    pub fn from_bounded(intervals: impl IntoIterator<Item = BoundedInterval<T>>) -> Self {
+        Self::from_intervals(intervals.into_iter().filter_map(|iv| iv.to_closed()))
+    }
+
+    /// Number of disjoint intervals.
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Number of points in the set.
+    pub fn total_size(&self) -> T::Size {
+        self.intervals.iter().map(Interval::size).sum()
+    }
+
+    /// Binary search for the interval holding `point`.
+    pub fn contains(&self, point: T) -> bool {
+        let idx = self.intervals.partition_point(|iv| iv.end < point);
+        self.intervals.get(idx).is_some_and(|iv| iv.start <= point)
+    }
+
+    /// Membership of many points at once: sorts the points and sweeps them against the
+    /// intervals, O(m log m + n) instead of one search per point.
+    pub fn contains_all(&self, points: &[T]) -> Membership {
+        let mut order: Vec<usize> = (0..points.len()).collect();
+        order.sort_unstable_by_key(|&i| points[i]);
+        let mut members = vec![false; points.len()];
+        let mut count = 0;
+        let mut ivs = self.intervals.iter().peekable();
+        for i in order {
+            let point = points[i];
+            while ivs.next_if(|iv| iv.end < point).is_some() {}
+            let Some(iv) = ivs.peek() else {
+                // every interval is behind us
+                break;
+            };
+            if iv.start <= point {
+                members[i] = true;
+                count += 1;
+            }
+        }
+        Membership { members, count }
+    }
+
+    /// Holes between consecutive intervals.
+    pub fn gaps(&self) -> impl Iterator<Item = Interval<T>> + '_ {
+        // neighbours are not adjacent, so there is a point on both sides of the gap
+        self.intervals
+            .windows(2)
+            .map(|w| Interval::new(w[0].end.succ().unwrap(), w[1].start.pred().unwrap()))
+    }
+
+    pub fn union(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        IntervalSet::from_intervals(self.iter().chain(other.iter()))
+    }
+
+    pub fn intersection(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let (a, b) = (&self.intervals, &other.intervals);
+        let mut intervals = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            let start = a[i].start.max(b[j].start);
+            let end = a[i].end.min(b[j].end);
+            if start <= end {
+                intervals.push(Interval::new(start, end));
+            }
+            // the one ending first can't meet anything else
+            if a[i].end < b[j].end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        // pieces of disjoint non-adjacent intervals stay that way
+        Self { intervals }
+    }
+
+    /// Points in `self` but not in `other`.
+    pub fn difference(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        let b = &other.intervals;
+        let mut intervals = Vec::new();
+        let mut j = 0;
+        for a in &self.intervals {
+            while j < b.len() && b[j].end < a.start {
+                j += 1;
+            }
+            // start of the part of `a` not yet cut away
+            let mut rest = Some(a.start);
+            for cut in b[j..].iter().take_while(|cut| cut.start <= a.end) {
+                let Some(from) = rest else { break };
+                // `cut.start > from` has a predecessor, `cut.end < a.end` a successor
+                if cut.start > from {
+                    intervals.push(Interval::new(from, cut.start.pred().unwrap()));
+                }
+                rest = if cut.end >= a.end { None } else { cut.end.succ() };
+            }
+            if let Some(from) = rest {
+                intervals.push(Interval::new(from, a.end));
+            }
+        }
+        Self { intervals }
+    }
+
+    /// Points in exactly one of the two sets.
+    pub fn symmetric_difference(&self, other: &IntervalSet<T>) -> IntervalSet<T> {
+        self.difference(other).union(&other.difference(self))
+    }
+
+    /// Points of `bounds` not in the set.
+    pub fn complement(&self, bounds: Interval<T>) -> IntervalSet<T> {
+        IntervalSet::from_intervals([bounds]).difference(self)
+    }
+}
+
+/// Result of [`IntervalSet::contains_all`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Membership {
+    /// Whether each query point is in the set, in query order.
+    pub members: Vec<bool>,
+    /// Number of query points in the set.
+    pub count: usize,
+}
+
+impl<T: Discrete> FromIterator<Interval<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        Self::from_intervals(iter)
+    }
+}
+
+/// How many times each point is covered: the line split into maximal segments of equal
+/// depth. Answers stabbing counts by binary search, the deepest overlap and how much
+/// length is covered exactly k times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage<T = u64> {
+    /// sorted, disjoint, every depth > 0, touching neighbours differ in depth
+    segments: Vec<(Interval<T>, usize)>,
+}
+
+impl<T> Default for Coverage<T> {
+    fn default() -> Self {
+        Self { segments: Vec::new() }
+    }
+}
+
+impl<T: Discrete> Coverage<T> {
+    pub fn from_intervals(intervals: impl IntoIterator<Item = Interval<T>>) -> Self {
+        // +1 where an interval starts, -1 just after it ends; an end at the largest value
+        // has no after, and its intervals stay open to the last segment
+        let mut events: Vec<(T, isize)> = Vec::new();
+        let mut top = None;
+        for iv in intervals {
+            events.push((iv.start, 1));
+            match iv.end.succ() {
+                Some(after) => events.push((after, -1)),
+                None => top = Some(iv.end),
+            }
+        }
+        events.sort_unstable();
+
+        let mut segments: Vec<(Interval<T>, usize)> = Vec::new();
+        let mut depth = 0isize;
+        let mut events = events.chunk_by(|a, b| a.0 == b.0).peekable();
+        while let Some(group) = events.next() {
+            let at = group[0].0;
+            depth += group.iter().map(|&(_, delta)| delta).sum::<isize>();
+            if depth == 0 {
+                continue;
+            }
+            // a later event has a predecessor, and depth stays above 0 after the last
+            // event only if some interval ends at `top`
+            let end = match events.peek() {
+                Some(next) => next[0].0.pred().unwrap(),
+                None => top.unwrap(),
+            };
+            match segments.last_mut() {
+                // one interval ended right where another began
+                Some((last, last_depth)) if *last_depth == depth as usize && last.end.succ() == Some(at) => last.end = end,
+                _ => segments.push((Interval::new(at, end), depth as usize)),
+            }
+        }
+        Self { segments }
+    }
+
+    /// Maximal runs of equal depth, sorted, leaving out uncovered points.
+    pub fn segments(&self) -> &[(Interval<T>, usize)] {
+        &self.segments
+    }
+
+    /// Number of intervals containing `point`.
+    pub fn depth_at(&self, point: T) -> usize {
+        let idx = self.segments.partition_point(|(iv, _)| iv.end < point);
+        match self.segments.get(idx) {
+            Some(&(iv, depth)) if iv.start <= point => depth,
+            _ => 0,
+        }
+    }
+
+    /// Largest number of intervals sharing a point, 0 when there are none.
+    pub fn max_depth(&self) -> usize {
+        self.segments.iter().map(|&(_, depth)| depth).max().unwrap_or(0)
+    }
+
+    /// `histogram[k]` is the number of points covered exactly k times. Index 0 counts the
+    /// gaps between the first and last covered point.
+    pub fn histogram(&self) -> Vec<T::Size> {
+        let mut histogram = vec![T::Size::default(); self.max_depth() + 1];
+        for &(iv, depth) in &self.segments {
+            histogram[depth] = histogram[depth] + iv.size();
+        }
+        for w in self.segments.windows(2) {
+            histogram[0] = histogram[0] + T::distance(w[0].0.end, w[1].0.start) - T::Size::from(1);
+        }
+        histogram
+    }
+}
+
+impl<T: Discrete> FromIterator<Interval<T>> for Coverage<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        Self::from_intervals(iter)
+    }
+}
+
+/// Which ranges contain each ingredient ID, found with a full point query on an
+/// [`IntervalTree`]. Ranges are referred to by their index in `ranges` and shown with
+/// their input line; empty ones match nothing and are reported as unused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    pub ranges: Vec<InputRange>,
+    /// Every ingredient ID in input order with the sorted indices of the ranges holding it.
+    pub ingredients: Vec<(u64, Vec<usize>)>,
+}
+
+impl Explanation {
+    pub fn new(ranges: Vec<InputRange>, ids: &[u64]) -> Self {
+        // the tree hands back intervals, equal ones come from several lines
+        let mut indices: AHashMap<Interval, Vec<usize>> = AHashMap::new();
+        for (i, r) in ranges.iter().enumerate() {
+            if let Some(iv) = r.range.to_closed() {
+                indices.entry(iv).or_default().push(i);
+            }
+        }
+        let tree = IntervalTree::from_intervals(indices.keys().copied());
+        let ingredients = ids
+            .iter()
+            .map(|&id| {
+                let mut matched: Vec<usize> = tree
+                    .query_point(id)
+                    .iter().flat_map(|iv| indices[iv].iter().copied()).collect();
+                matched.sort_unstable();
+                (id, matched)
+            })
+            .collect();
+        Self { ranges, ingredients }
+    }
+
+    /// Number of IDs in at least one range, the part 1 answer.
+    pub fn fresh_count(&self) -> usize {
+        self.ingredients.iter().filter(|(_, matched)| !matched.is_empty()).count()
+    }
+
+    /// IDs in no range, in input order.
+    pub fn unmatched_ids(&self) -> Vec<u64> {
+        self.ingredients.iter().filter(|(_, matched)| matched.is_empty()).map(|&(id, _)| id).collect()
+    }
+
+    /// Indices of the ranges holding none of the IDs.
+    pub fn unused_ranges(&self) -> Vec<usize> {
+        let mut used = vec![false; self.ranges.len()];
+        for i in self.ingredients.iter().flat_map(|(_, matched)| matched) {
+            used[*i] = true;
+        }
+        (0..self.ranges.len()).filter(|&i| !used[i]).collect()
+    }
+
+    /// Aligned text: one row per ID with its ranges, then the unmatched IDs and unused
+    /// ranges. Ranges show as `line 3 10-14`.
+    pub fn to_table(&self) -> String {
+        use std::fmt::Write;
+        let range = |i: usize| self.ranges[i].to_string();
+        let width = self.ingredients.iter().map(|(id, _)| id.to_string().len()).max().unwrap_or(0).max(2);
+        let list = |items: &[String]| if items.is_empty() { "-".to_string() } else { items.join(", ") };
+        let mut table = format!("{:<width$}  RANGES\n", "ID");
+        for (id, matched) in &self.ingredients {
+            let ranges: Vec<String> = matched.iter().map(|&i| range(i)).collect();
+            writeln!(table, "{:<width$}  {}", id, list(&ranges)).unwrap();
+        }
+        let ids: Vec<String> = self.unmatched_ids().iter().map(u64::to_string).collect();
+        writeln!(table, "\nUnmatched IDs ({}): {}", ids.len(), list(&ids)).unwrap();
+        let unused: Vec<String> = self.unused_ranges().into_iter().map(range).collect();
+        writeln!(table, "Unused ranges ({}): {}", unused.len(), list(&unused)).unwrap();
+        table
+    }
+
+    /// The same report as a JSON object with `ingredients`, `unmatched_ids` and
+    /// `unused_ranges`, ranges as `{"line", "start", "end", "bounds"}` where bounds is
+    /// the bracket pair, like `"[)"`.
+    pub fn to_json(&self) -> String {
+        let range = |i: usize| {
+            let InputRange { line, range: r } = self.ranges[i];
+            let open = if r.start_kind == BoundKind::Closed { '[' } else { '(' };
+            let close = if r.end_kind == BoundKind::Closed { ']' } else { ')' };
+            format!("{{\"line\":{},\"start\":{},\"end\":{},\"bounds\":\"{}{}\"}}", line, r.start, r.end, open, close)
+        };
+        let list = |items: Vec<String>| format!("[{}]", items.join(","));
+        let ingredients: Vec<String> = self
+            .ingredients
+            .iter()
+            .map(|(id, matched)| format!("{{\"id\":{},\"ranges\":{}}}", id, list(matched.iter().map(|&i| range(i)).collect())))
+            .collect();
+        format!(
+            "{{\"ingredients\":{},\"unmatched_ids\":{},\"unused_ranges\":{}}}",
+            list(ingredients),
+            list(self.unmatched_ids().iter().map(u64::to_string).collect()),
+            list(self.unused_ranges().into_iter().map(range).collect()),
+        )
+    }
 }
 
 #[cfg(test)]
@@ -203,10 +1432,462 @@ mod test {
     fn test_intervals() {
         let intervals : Vec<Interval >= ["0-4", "2-5", "3-6", "1-7"].iter().map(|s| s.parse().unwrap()).collect();
 
-        let root = construct_interval_node(intervals).unwrap();
-        assert!(overlap_any_point(Some(&root), 1));
-        assert!(overlap_any_point(Some(&root), 2));
-        assert!(overlap_any_point(Some(&root), 4));
+        let tree = IntervalTree::from_intervals(intervals);
+        assert!(tree.contains_point(1));
+        assert!(tree.contains_point(2));
+        assert!(tree.contains_point(4));
+
+    }
+
+    /// Deterministic random intervals (xorshift) with endpoints below `max`.
+    fn random_intervals(count: usize, max: u64, seed: u64) -> Vec<Interval> {
+        let mut next = crate::test_rng::xorshift(seed);
+        (0..count)
+            .map(|_| {
+                let start = next() % max;
+                let len = next() % (max / 8).max(1);
+                Interval::new(start, start + len)
+            })
+            .collect()
+    }
+
+    fn sorted(mut ivs: Vec<Interval>) -> Vec<Interval> {
+        ivs.sort_by_key(|iv| (iv.start, iv.end));
+        ivs
+    }
+
+    #[test]
+    fn test_interval_tree_against_scan() {
+        let intervals = random_intervals(200, 1000, 0x9E3779B97F4A7C15);
+        let tree: IntervalTree = intervals.iter().copied().collect();
+        assert_eq!(tree.len(), 200);
+        for point in 0..1200 {
+            let expected: Vec<_> = intervals.iter().copied().filter(|iv| iv.intersects(point)).collect();
+            assert_eq!(tree.contains_point(point), !expected.is_empty());
+            assert_eq!(sorted(tree.query_point(point)), sorted(expected), "point: {}", point);
+        }
+        for range in random_intervals(200, 1200, 42) {
+            let expected: Vec<_> = intervals.iter().copied().filter(|iv| iv.overlaps(&range)).collect();
+            assert_eq!(sorted(tree.query_range(range)), sorted(expected), "range: {:?}", range);
+        }
+
+        let empty: IntervalTree<i32> = IntervalTree::from_intervals([]);
+        assert!(empty.is_empty());
+        assert!(!empty.contains_point(3));
+        assert!(empty.query_range(Interval::new(-5, 5)).is_empty());
+    }
+
+    #[test]
+    fn test_dynamic_tree_against_scan() {
+        let mut next = crate::test_rng::xorshift(0xDEADBEEF);
+        let pool = random_intervals(60, 500, 7);
+        let mut tree = DynamicIntervalTree::new();
+        let mut scan: Vec<Interval> = Vec::new();
+        for step in 0..3000 {
+            let iv = pool[(next() % pool.len() as u64) as usize];
+            if next().is_multiple_of(3) {
+                let pos = scan.iter().position(|x| *x == iv);
+                assert_eq!(tree.remove(&iv), pos.is_some());
+                if let Some(pos) = pos {
+                    scan.swap_remove(pos);
+                }
+            } else {
+                tree.insert(iv);
+                scan.push(iv);
+            }
+            assert_eq!(tree.len(), scan.len());
+            // AVL bound: h < 1.45 log2(n + 2)
+            assert!((tree.height() as f64) < 1.45 * ((scan.len() + 2) as f64).log2(), "step {}", step);
+
+            if step % 100 == 0 {
+                let bulk = DynamicIntervalTree::from_intervals(scan.iter().copied());
+                let fixed = IntervalTree::from_intervals(scan.iter().copied());
+                for point in (0..600).step_by(7) {
+                    let expected = sorted(scan.iter().copied().filter(|iv| iv.intersects(point)).collect());
+                    assert_eq!(tree.query_point(point), expected);
+                    assert_eq!(bulk.query_point(point), expected);
+                    assert_eq!(sorted(fixed.query_point(point)), expected);
+                }
+                for point in 0..600 {
+                    let expected = scan.iter().any(|iv| iv.intersects(point));
+                    assert_eq!(tree.contains_point(point), expected, "point {}", point);
+                    assert_eq!(bulk.contains_point(point), expected, "point {}", point);
+                }
+                let start = next() % 600;
+                let range = Interval::new(start, start + next() % 50);
+                let expected = sorted(scan.iter().copied().filter(|iv| iv.overlaps(&range)).collect());
+                assert_eq!(tree.query_range(range), expected);
+                assert_eq!(bulk.query_range(range), expected);
+                assert_eq!(sorted(fixed.query_range(range)), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_interval_set() {
+        let set: IntervalSet = ["3-5", "10-14", "16-20", "12-18"].iter().map(|s| s.parse().unwrap()).collect();
+        assert_eq!(set.intervals(), [Interval::new(3, 5), Interval::new(10, 20)]);
+        assert_eq!(set.total_size(), 14);
+        assert_eq!(set.gaps().collect::<Vec<_>>(), [Interval::new(6, 9)]);
+        assert!(set.contains(5) && set.contains(10) && !set.contains(9) && !set.contains(21));
+        // touching intervals merge
+        let touching = IntervalSet::from_intervals([Interval::new(1, 3), Interval::new(4, 6)]);
+        assert_eq!(touching.intervals(), [Interval::new(1, 6)]);
+        assert_eq!(
+            set.complement(Interval::new(0, 25)).intervals(),
+            [Interval::new(0, 2), Interval::new(6, 9), Interval::new(21, 25)]
+        );
+        let max = IntervalSet::from_intervals([Interval::new(u64::MAX - 1, u64::MAX), Interval::new(5, u64::MAX - 2)]);
+        assert_eq!(max.intervals(), [Interval::new(5, u64::MAX)]);
+    }
+
+    #[test]
+    fn test_interval_set_against_bitmap() {
+        const UNIVERSE: u64 = 300;
+        let bitmap = |set: &IntervalSet| (0..UNIVERSE).map(|p| set.contains(p)).collect::<Vec<_>>();
+        let bitmap_of = |ivs: &[Interval]| (0..UNIVERSE).map(|p| ivs.iter().any(|iv| iv.intersects(p))).collect::<Vec<_>>();
+        for seed in 1..40 {
+            let a_ivs = random_intervals(12, 250, seed);
+            let b_ivs = random_intervals(12, 250, seed * 7919);
+            let (a, b): (IntervalSet, IntervalSet) = (a_ivs.iter().copied().collect(), b_ivs.iter().copied().collect());
+            let (ma, mb) = (bitmap_of(&a_ivs), bitmap_of(&b_ivs));
+            assert_eq!(bitmap(&a), ma);
+            assert_eq!(a.total_size(), ma.iter().filter(|&&x| x).count() as u64);
+
+            let combine = |f: fn(bool, bool) -> bool| ma.iter().zip(&mb).map(|(&x, &y)| f(x, y)).collect::<Vec<_>>();
+            let ops = [
+                (a.union(&b), combine(|x, y| x || y)),
+                (a.intersection(&b), combine(|x, y| x && y)),
+                (a.difference(&b), combine(|x, y| x && !y)),
+                (a.symmetric_difference(&b), combine(|x, y| x != y)),
+                (a.complement(Interval::new(0, UNIVERSE - 1)), combine(|x, _| !x)),
+            ];
+            for (i, (set, expected)) in ops.into_iter().enumerate() {
+                assert_eq!(bitmap(&set), expected, "op {} seed {}", i, seed);
+                // still normalized
+                assert_eq!(set, IntervalSet::from_intervals(set.iter()), "op {} seed {}", i, seed);
+            }
+
+            let gap_points: u64 = a.gaps().map(|gap| gap.size()).sum();
+            if let (Some(first), Some(last)) = (a.intervals().first(), a.intervals().last()) {
+                assert_eq!(gap_points + a.total_size(), last.end - first.start + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_contains_all() {
+        let ivs = random_intervals(150, 5000, 0xC0FFEE);
+        let set: IntervalSet = ivs.iter().copied().collect();
+        let tree = IntervalTree::from_intervals(ivs.iter().copied());
+        // unsorted with duplicates, past both ends
+        let points: Vec<u64> = random_intervals(2000, 6000, 99).into_iter().flat_map(|iv| [iv.start, iv.start, iv.end]).collect();
+        let membership = set.contains_all(&points);
+        let expected: Vec<bool> = points.iter().map(|&p| tree.contains_point(p)).collect();
+        assert_eq!(membership.members, expected);
+        assert_eq!(membership.count, expected.iter().filter(|&&m| m).count());
+
+        assert_eq!(IntervalSet::new().contains_all(&[1, 2]), Membership { members: vec![false, false], count: 0 });
+        assert_eq!(set.contains_all(&[]).count, 0);
+    }
+
+    #[test]
+    fn test_bounded_intervals() {
+        let parse = |s: &str| s.parse::<BoundedInterval>().unwrap();
+        assert_eq!(parse("3-7"), BoundedInterval::closed(3, 7));
+        assert_eq!(parse("[3,7)"), BoundedInterval::half_open(3, 7));
+        assert_eq!(parse("( 3 , 7 )"), BoundedInterval::open(3, 7));
+        assert_eq!(parse("(3,7]").to_string(), "(3,7]");
+        for bad in ["[3,7", "[3;7]", "[a,7]", "3"] {
+            assert!(bad.parse::<BoundedInterval>().is_err(), "{}", bad);
+        }
+
+        assert_eq!(parse("[3,7]").size(), 5);
+        assert_eq!(parse("[3,7)").size(), 4);
+        assert_eq!(parse("(3,7)").size(), 3);
+        assert_eq!(parse("[3,3)").size(), 0);
+        assert_eq!(parse("(0,0)").to_closed(), None);
+        assert_eq!(parse("(3,4)").to_closed(), None);
+        assert_eq!(BoundedInterval::open(u64::MAX, u64::MAX).to_closed(), None);
+
+        assert_eq!(parse("[1,3)").merge(&parse("[3,5)")), Some(parse("[1,5)")));
+        // {1, 2} and {4} leave 3 out
+        assert_eq!(parse("[1,3)").merge(&parse("(3,4]")), None);
+        assert_eq!(parse("[1,3]").merge(&parse("(3,4]")), Some(parse("[1,4]")));
+        assert_eq!(parse("[1,5)").intersection(&parse("(1,5]")), parse("(1,5)"));
+
+        let set = IntervalSet::from_bounded([parse("[3,5)"), parse("(9,14]"), parse("[5,5]"), parse("[20,20)")]);
+        assert_eq!(set.intervals(), [Interval::new(3, 5), Interval::new(10, 14)]);
+    }
+
+    #[test]
+    fn test_bounded_against_points() {
+        let kinds = [BoundKind::Closed, BoundKind::Open];
+        let mut all = Vec::new();
+        for (start, end) in [(2, 2), (2, 3), (2, 4), (3, 6), (4, 5), (5, 9), (7, 8)] {
+            for start_kind in kinds {
+                for end_kind in kinds {
+                    all.push(BoundedInterval::new(start, start_kind, end, end_kind));
+                }
+            }
+        }
+        let points = |iv: &BoundedInterval| {
+            (0..12)
+                .filter(|&p| {
+                    let after = p > iv.start || (p == iv.start && iv.start_kind == BoundKind::Closed);
+                    let before = p < iv.end || (p == iv.end && iv.end_kind == BoundKind::Closed);
+                    after && before
+                })
+                .collect::<Vec<u64>>()
+        };
+        for a in &all {
+            let pa = points(a);
+            assert_eq!(a.size(), pa.len() as u64, "{}", a);
+            assert!((0..12).all(|p| a.contains(p) == pa.contains(&p)), "{}", a);
+            for b in &all {
+                let pb = points(b);
+                let both: Vec<u64> = pa.iter().copied().filter(|p| pb.contains(p)).collect();
+                assert_eq!(points(&a.intersection(b)), both, "{} & {}", a, b);
+
+                let mut either: Vec<u64> = pa.iter().chain(&pb).copied().collect();
+                either.sort();
+                either.dedup();
+                let contiguous = either.windows(2).all(|w| w[1] == w[0] + 1);
+                match a.merge(b) {
+                    Some(merged) => assert_eq!(points(&merged), either, "{} | {}", a, b),
+                    None => assert!(!contiguous, "{} | {}", a, b),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_interval_validation() {
+        assert_eq!(" 3-7\t".parse::<Interval>().unwrap(), Interval::new(3, 7));
+        assert_eq!("3 - 7".parse::<Interval>().unwrap(), Interval::new(3, 7));
+        assert_eq!("5-5".parse::<Interval>().unwrap().size(), 1);
+        for bad in ["9-3", "1-2-3", "1--2", "-3", "3-", "37", "", "a-b", "[7,3]", "[1,2,3]"] {
+            assert!(bad.parse::<Interval>().is_err(), "{:?}", bad);
+            assert!(bad.parse::<BoundedInterval>().is_err(), "{:?}", bad);
+        }
+        let err = "9-3".parse::<Interval>().unwrap_err().to_string();
+        assert!(err.contains("reversed"), "{}", err);
+    }
+
+    #[test]
+    fn test_inventory_parser() {
+        let input = "3-5\r\n 10-14 \r\n16-20\r\n[12,19)\r\n\r\n1\r\n5\r\n 8\r\n11\r\n17\r\n32\r\n\r\n\r\n";
+        let (intervals, ids) = InventoryParser::default().parse(input).unwrap();
+        assert_eq!(intervals, [Interval::new(3, 5), Interval::new(10, 14), Interval::new(16, 20), Interval::new(12, 18)]);
+        assert_eq!(ids, [1, 5, 8, 11, 17, 32]);
+        assert_eq!(IntervalSet::from_intervals(intervals.iter().copied()).contains_all(&ids).count, 3);
+
+        let err = |parser: InventoryParser, input: &str| format!("{:#}", parser.parse(input).unwrap_err());
+        let e = err(InventoryParser::default(), "3-5\n9-3\n\n4\n");
+        assert!(e.contains("line 2") && e.contains("reversed"), "{}", e);
+        let e = err(InventoryParser::default(), "3-5\n1-2-3\n\n4\n");
+        assert!(e.contains("line 2"), "{}", e);
+        let e = err(InventoryParser::default(), "3-5\n\n4\n\n5\n");
+        assert!(e.contains("line 4"), "{}", e);
+        let e = err(InventoryParser::default(), "3-5\n\n4\n7-8\n");
+        assert!(e.contains("line 4"), "{}", e);
+        let e = err(InventoryParser::default(), "3-5\n4-6\n");
+        assert!(e.contains("missing blank line"), "{}", e);
+
+        let (intervals, ids) = InventoryParser::default().parse("3-5\n\n").unwrap();
+        assert_eq!((intervals, ids), (vec![Interval::new(3, 5)], vec![]));
+        let (intervals, ids) = InventoryParser::default().parse("3-5\r\n\r\n\r\n").unwrap();
+        assert_eq!((intervals, ids), (vec![Interval::new(3, 5)], vec![]));
+
+        let (intervals, _) = InventoryParser::default().normalize_reversed(true).parse("9-3\n(7,3]\n\n4\n").unwrap();
+        assert_eq!(intervals, [Interval::new(3, 9), Interval::new(3, 6)]);
+    }
+
+    #[test]
+    fn test_coverage() {
+        let ivs: Vec<Interval> = ["3-5", "10-14", "16-20", "12-18"].iter().map(|s| s.parse().unwrap()).collect();
+        let coverage: Coverage = ivs.iter().copied().collect();
+        assert_eq!(
+            coverage.segments(),
+            [
+                (Interval::new(3, 5), 1),
+                (Interval::new(10, 11), 1),
+                (Interval::new(12, 14), 2),
+                (Interval::new(15, 15), 1),
+                (Interval::new(16, 18), 2),
+                (Interval::new(19, 20), 1),
+            ]
+        );
+        assert_eq!(coverage.max_depth(), 2);
+        assert_eq!(coverage.histogram(), [4, 8, 6]);
+        assert_eq!(Coverage::<u64>::default().histogram(), [0]);
+        let touching = Coverage::from_intervals([Interval::new(1, 3), Interval::new(4, 6)]);
+        assert_eq!(touching.segments(), [(Interval::new(1, 6), 1)]);
+
+        let edge = Coverage::from_intervals([Interval::new(5, u64::MAX), Interval::new(u64::MAX, u64::MAX)]);
+        assert_eq!(edge.depth_at(u64::MAX), 2);
+        assert_eq!(edge.depth_at(4), 0);
+    }
+
+    #[test]
+    fn test_coverage_against_scan() {
+        for seed in 1..20 {
+            let ivs = random_intervals(40, 400, seed);
+            let coverage = Coverage::from_intervals(ivs.iter().copied());
+            let tree = IntervalTree::from_intervals(ivs.iter().copied());
+            let dynamic = DynamicIntervalTree::from_intervals(ivs.iter().copied());
+            let depths: Vec<usize> = (0..500).map(|p| ivs.iter().filter(|iv| iv.intersects(p)).count()).collect();
+            for (p, &depth) in depths.iter().enumerate() {
+                let p = p as u64;
+                assert_eq!(coverage.depth_at(p), depth, "seed {} point {}", seed, p);
+                assert_eq!(tree.stab_count(p), depth, "seed {} point {}", seed, p);
+                assert_eq!(dynamic.stab_count(p), depth, "seed {} point {}", seed, p);
+            }
+            assert_eq!(coverage.max_depth(), *depths.iter().max().unwrap());
+
+            let first = depths.iter().position(|&d| d > 0).unwrap();
+            let last = depths.iter().rposition(|&d| d > 0).unwrap();
+            let mut histogram = vec![0; coverage.max_depth() + 1];
+            for &depth in &depths[first..=last] {
+                histogram[depth] += 1;
+            }
+            assert_eq!(coverage.histogram(), histogram, "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn test_tree_stats() {
+        let tree: IntervalTree = ["0-4", "2-5", "3-6", "1-7", "10-12"].iter().map(|s| s.parse().unwrap()).collect();
+        let stats = tree.stats();
+        assert_eq!(stats.intervals_per_node.iter().sum::<usize>(), 5);
+        assert_eq!(stats.node_count(), stats.intervals_per_node.len());
+        assert!(stats.intervals_per_node.iter().all(|&count| count > 0));
+        let dot = tree.to_dot();
+        assert!(dot.starts_with("digraph interval_tree {"), "{}", dot);
+        assert_eq!(dot.matches("label=\"center").count(), stats.node_count());
+        assert_eq!(dot.matches(" -> ").count(), stats.node_count() - 1);
+        assert_eq!(IntervalTree::<u64>::from_intervals([]).stats(), TreeStats::default());
+
+        // nested, chained and point intervals all keep the depth logarithmic
+        let n = 1 << 14;
+        let shapes: [Vec<Interval>; 3] = [
+            (0..n).map(|i| Interval::new(i, 2 * n - i)).collect(),
+            (0..n).map(|i| Interval::new(i, i + 1)).collect(),
+            (0..n).map(|i| Interval::new(i, i)).collect(),
+        ];
+        for ivs in shapes {
+            let tree = IntervalTree::from_intervals(ivs.iter().copied());
+            let stats = tree.stats();
+            assert_eq!(stats.intervals_per_node.iter().sum::<usize>(), n as usize);
+            assert!(stats.depth <= 15, "depth {}", stats.depth);
+            assert!(tree.contains_point(n / 2) && tree.stab_count(n - 1) > 0);
+        }
+    }
+
+    #[test]
+    fn test_signed_and_wide_endpoints() {
+        let iv: Interval<i64> = "-5--3".parse().unwrap();
+        assert_eq!(iv, Interval::new(-5, -3));
+        assert_eq!(iv.size(), 3u64);
+        assert_eq!(" -7 - 2 ".parse::<Interval<i32>>().unwrap(), Interval::new(-7, 2));
+        assert!("-3--5".parse::<Interval<i64>>().is_err());
+        assert!("1-2-3".parse::<Interval<i64>>().is_err());
+        assert_eq!(Interval::new(i64::MIN, i64::MAX - 1).size(), u64::MAX);
+        assert_eq!("(-3,3)".parse::<BoundedInterval<i8>>().unwrap().size(), 5);
+        assert_eq!(BoundedInterval::open(i8::MIN, i8::MIN + 1).to_closed(), None);
+
+        let set = IntervalSet::from_intervals([Interval::new(-10i64, -1), Interval::new(0, 4), Interval::new(8, 9)]);
+        assert_eq!(set.intervals(), [Interval::new(-10, 4), Interval::new(8, 9)]);
+        assert_eq!(set.total_size(), 17);
+        assert_eq!(set.complement(Interval::new(-12, 12)).total_size(), 8);
+        assert_eq!(set.contains_all(&[-11, -10, 5, 9]).members, [false, true, false, true]);
+
+        let big = u128::MAX - 10;
+        let wide: Interval<u128> = format!("{}-{}", big, u128::MAX).parse().unwrap();
+        assert_eq!(wide.size(), 11);
+        let coverage = Coverage::from_intervals([wide, Interval::new(0, big)]);
+        assert_eq!(coverage.depth_at(big), 2);
+        assert_eq!(coverage.depth_at(u128::MAX), 1);
+        assert_eq!(coverage.max_depth(), 2);
+
+        let tree = IntervalTree::from_intervals([Interval::new(-5i32, 5), Interval::new(-20, -6)]);
+        assert_eq!(tree.query_point(-6), [Interval::new(-20, -6)]);
+    }
+
+    #[test]
+    fn test_date_endpoints() {
+        for (text, days) in [("1970-01-01", 0), ("2000-02-29", 11016), ("1969-12-31", -1), ("2025-12-31", 20453)] {
+            let date: Date = text.parse().unwrap();
+            assert_eq!(date, Date(days), "{}", text);
+            assert_eq!(date.to_string(), text);
+        }
+        for bad in ["2025-02-29", "2025-13-01", "2025-00-10", "2025-01", "2025-01-xx"] {
+            assert!(bad.parse::<Date>().is_err(), "{}", bad);
+        }
+        // every day over four centuries round-trips
+        for days in -146097..146097 {
+            let (y, m, d) = Date(days).to_ymd();
+            assert_eq!(Date::from_ymd(y, m, d).unwrap(), Date(days));
+        }
+
+        let expiry: Interval<Date> = "2024-02-01-2024-03-01".parse().unwrap();
+        assert_eq!(expiry.size(), 30);
+        let stock = IntervalSet::from_intervals([expiry, "2024-03-02-2024-03-31".parse().unwrap()]);
+        assert_eq!(stock.len(), 1);
+        assert!(stock.contains("2024-02-29".parse().unwrap()));
+        let quarter = "[2024-01-01,2024-04-01)".parse::<BoundedInterval<Date>>().unwrap();
+        assert_eq!(quarter.size(), 91);
+        assert_eq!(stock.complement(quarter.to_closed().unwrap()).total_size(), 31);
+    }
+
+    #[test]
+    fn test_float_endpoints() {
+        let iv: Interval<TotalF64> = "-1.5-2.25".parse().unwrap();
+        assert_eq!(iv.length(), 3.75);
+        assert!(iv.intersects(TotalF64(-0.0)));
+        let tree = IntervalTree::from_intervals([iv, Interval::new(TotalF64(2.0), TotalF64(f64::INFINITY))]);
+        assert_eq!(tree.stab_count(TotalF64(2.1)), 2);
+        assert_eq!(tree.stab_count(TotalF64(1e300)), 1);
+        assert!(!tree.contains_point(TotalF64(f64::NAN)));
+        assert!(TotalF64(-0.0) < TotalF64(0.0));
+    }
+
+    #[test]
+    fn test_explanation() {
+        let input = "3-5\n10-14\n16-20\n12-18\n40-50\n3-5\n\n1\n5\n8\n11\n17\n32\n";
+        let (ranges, ids) = InventoryParser::default().parse_lines(input).unwrap();
+        let explanation = Explanation::new(ranges, &ids);
+        assert_eq!(
+            explanation.ingredients,
+            [(1, vec![]), (5, vec![0, 5]), (8, vec![]), (11, vec![1]), (17, vec![2, 3]), (32, vec![])]
+        );
+        assert_eq!(explanation.fresh_count(), 3);
+        assert_eq!(explanation.unmatched_ids(), [1, 8, 32]);
+        assert_eq!(explanation.unused_ranges(), [4]);
+
+        let table = explanation.to_table();
+        assert!(table.starts_with("ID  RANGES\n1   -\n5   line 1 3-5, line 6 3-5\n"), "{}", table);
+        assert!(table.contains("17  line 3 16-20, line 4 12-18\n"), "{}", table);
+        assert!(table.ends_with("Unmatched IDs (3): 1, 8, 32\nUnused ranges (1): line 5 40-50\n"), "{}", table);
+
+        let json = explanation.to_json();
+        let range = |line, start, end| format!(r#"{{"line":{},"start":{},"end":{},"bounds":"[]"}}"#, line, start, end);
+        let prefix = format!(r#"{{"ingredients":[{{"id":1,"ranges":[]}},{{"id":5,"ranges":[{},{}]}}"#, range(1, 3, 5), range(6, 3, 5));
+        assert!(json.starts_with(&prefix), "{}", json);
+        let suffix = format!(r#""unmatched_ids":[1,8,32],"unused_ranges":[{}]}}"#, range(5, 40, 50));
+        assert!(json.ends_with(&suffix), "{}", json);
+
+        // empty ranges keep their place and are never matched
+        let (ranges, ids) = InventoryParser::default().parse_lines("[3,3)\n5-6\n\n5").unwrap();
+        let explanation = Explanation::new(ranges, &ids);
+        assert_eq!(explanation.ingredients, [(5, vec![1])]);
+        assert_eq!(explanation.unused_ranges(), [0]);
+        let table = explanation.to_table();
+        assert!(table.contains("5   line 2 5-6\n") && table.ends_with("Unused ranges (1): line 1 [3,3)\n"), "{}", table);
+        assert!(explanation.to_json().contains(r#""unused_ranges":[{"line":1,"start":3,"end":3,"bounds":"[)"}]"#));
 
+        let empty = Explanation::new(vec![], &[]);
+        assert!(empty.to_table().ends_with("Unmatched IDs (0): -\nUnused ranges (0): -\n"));
+        assert_eq!(empty.to_json(), r#"{"ingredients":[],"unmatched_ids":[],"unused_ranges":[]}"#);
     }
 }
@@ -6,3 +6,16 @@ pub mod day4;
 pub mod day5;
 pub mod day6;
 pub mod day7;
+
+#[cfg(test)]
+mod test_rng {
+    /// Deterministic xorshift64 generator. `seed` must not be zero.
+    pub fn xorshift(mut seed: u64) -> impl FnMut() -> u64 {
+        move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        }
+    }
+}
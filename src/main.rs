@@ -8,6 +8,7 @@ fn main() -> anyhow::Result<()> {
         Some(d) => d.parse()?,
         None => {
             eprintln!("Usage: cargo run -- <day:u32>");
+            eprintln!("       cargo run -- 5 explain [json]");
             return Ok(());
         }
     };
@@ -21,6 +22,9 @@ fn main() -> anyhow::Result<()> {
         2 => day2::solve(),
         3 => day3::solve(),
         4 => day4::solve(),
+        5 if args.get(2).is_some_and(|mode| mode == "explain") => {
+            day5::explain(args.get(3).is_some_and(|format| format == "json"))
+        }
         5 => day5::solve(),
         6 => day6::solve(),
         7 => day7::solve(),